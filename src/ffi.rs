@@ -19,11 +19,13 @@ mod handle_shared;
 mod null;
 mod out;
 mod reference;
+mod stream_buffer;
 
 pub use null::IsNull;
 pub use out::Out;
 
 pub use ffi_result::{
+    ErrorInfo,
     FFIResult,
     FFIResultKind,
     QuinnError,
@@ -34,6 +36,8 @@ pub use reference::{
     RefMut,
 };
 
+pub use stream_buffer::StreamBuffer;
+
 pub use bindings::{
     accept_stream,
     connect_client,
@@ -2,6 +2,7 @@ use crate::{
     ffi::{
         ConnectionHandle,
         EndpointHandle,
+        ErrorInfo,
         FFIResult,
         FFIResultKind,
         HandleMut,
@@ -9,26 +10,41 @@ use crate::{
         Ref,
         RustlsClientConfigHandle,
         RustlsServerConfigHandle,
+        StreamBuffer,
     },
     proto::{
-        ClientConfig,
         DatagramEvent,
         Dir,
         Endpoint,
         EndpointConfig,
         ReadError,
-        ServerConfig,
         StreamId,
     },
     proto_impl::{
+        decode_alpn_protocols,
+        decode_certificate_chain,
+        register_endpoint,
+        store_pending_metrics,
+        take_pending_or_render_prometheus,
+        ClientConfigImpl,
         ConnectionImpl,
+        ConnectionSide,
+        ConnectionStats,
         EndpointImpl,
         EndpointPoller,
+        EndpointStats,
+        ErrorCategory,
         FFIErrorKind,
         IpAddr,
+        QlogSink,
+        SelfSignedCertificate,
+        ServerConfigImpl,
+        SniCertResolver,
+        StreamReadStatus,
+        StreamStats,
+        ZeroRttState,
     },
 };
-use bytes::BytesMut;
 use libc::size_t;
 use quinn_proto::{
     VarInt,
@@ -42,12 +58,19 @@ use rustls::{
 use std::{
     io::Write,
     net::SocketAddr,
+    path::PathBuf,
     sync::Arc,
-    time::Instant,
+    time::Duration,
 };
 
 use Into;
 
+#[cfg(feature = "key-log")]
+use crate::proto_impl::{
+    CallbackKeyLog,
+    FileKeyLog,
+};
+
 ffi! {
     /// Creates a server endpoint with a certain configuration.
     ///
@@ -60,15 +83,19 @@ ffi! {
         let endpoint_config = Arc::new(EndpointConfig::default());
 
         let mut endpoint = None;
-        let _ = handle.ref_access(&mut |server_config| {
-           endpoint = Some(Endpoint::new(endpoint_config.clone(), Some(Arc::from(server_config.clone()))));
+        let result = handle.ref_access(&mut |server_config| {
+           endpoint = Some(Endpoint::new(endpoint_config.clone(), Some(Arc::new(server_config.proto_config()))));
            Ok(())
         });
 
-        let endpoint = EndpointImpl::new(endpoint.unwrap());
+        let endpoint = match endpoint {
+            Some(endpoint) => EndpointImpl::new(endpoint),
+            None => return result.into(),
+        };
         let endpoint_id = endpoint.id;
 
         let mut endpoint_handle = EndpointHandle::new(endpoint);
+        register_endpoint(&endpoint_handle);
 
         let mut result = FFIResult::ok();
 
@@ -103,14 +130,19 @@ ffi! {
         let mut proto_endpoint = Endpoint::new(endpoint_config, None);
         let mut endpoint = EndpointImpl::new(proto_endpoint);
 
-        let _ = handle.ref_access(&mut |client_config| {
-          endpoint.set_default_client_config(client_config.clone());
+        let result = handle.ref_access(&mut |client_config| {
+          endpoint.set_default_client_config(client_config.proto_config());
            Ok(())
         });
 
+        if result.is_err() {
+            return result.into();
+        }
+
         let endpoint_identifier = endpoint.id;
 
         let endpoint = EndpointHandle::new(endpoint);
+        register_endpoint(&endpoint);
 
         let (poller, poll_notifier) = EndpointPoller::new(endpoint.clone());
         poller.start_polling();
@@ -134,12 +166,17 @@ ffi! {
     /// * `out_connection_id`: Allocated memory for the connection id of the new connection.
     ///
     /// Use the returned `ConnectionHandle` for connection related FFI functions.
+    ///
+    /// 0-RTT is attempted when early data is enabled on the client configuration and a resumption ticket for the server is cached.
+    /// Use `connection_zero_rtt_state` to check if 0-RTT data can be sent, the `on_zero_rtt_resolved` callback reports whether the server accepted it.
+    /// If `on_zero_rtt_resolved` reports `Rejected`, the streams opened during 0-RTT are discarded and their data has to be sent again on new streams.
     fn connect_client(handle: EndpointHandle,host_bytes: Ref<u8>,host_bytes_len: u32,address: IpAddr,out_connection: Out<ConnectionHandle>,out_connection_id: Out<u32>) -> FFIResult {
-        let host_bytes = unsafe {host_bytes.as_bytes(host_bytes_len as usize).to_vec()};
-        let host_name = String::from_utf8(host_bytes).expect("Key path not in utf8 format");
+        let host_bytes = unsafe { host_bytes.as_bytes(host_bytes_len as usize) };
 
         handle.mut_access(&mut |endpoint| {
-            let mut connection = endpoint.connect(address.into(), &host_name).unwrap();
+            let host_name = std::str::from_utf8(host_bytes)
+                .map_err(|_| FFIErrorKind::invalid_argument("Host name not in utf8 format"))?;
+            let mut connection = endpoint.connect(address.into(), host_name)?;
             connection.mark_pollable()?;
 
             let c_handle = connection.connection_handle;
@@ -167,10 +204,7 @@ ffi! {
 
             let addr: SocketAddr = address.into();
 
-            match endpoint
-                .inner
-                .handle(Instant::now(), addr, None, None, BytesMut::from(slice))
-            {
+            match endpoint.handle_datagram(addr, slice) {
                 Some((handle, DatagramEvent::NewConnection(conn))) => {
                     let mut connection = endpoint.add_connection(handle, conn);
 
@@ -193,13 +227,75 @@ ffi! {
                     endpoint.poll_connection(handle)?;
                 }
                 None => {
-                    println!("None handled");
+                    tracing::trace!("Datagram from {} did not belong to a connection", addr);
                 }
             }
             Ok(())
         }).into()
     }
 
+    /// Retrieves a snapshot of the counters of an endpoint.
+    ///
+    /// * `handle`: Valid `EndpointHandle` pointer for the duration of the function call.
+    /// * `out_stats`: Allocated memory for the endpoint statistics.
+    fn endpoint_stats(handle: EndpointHandle, out_stats: Out<EndpointStats>) -> FFIResult {
+        handle.ref_access(&mut |endpoint| {
+            unsafe { out_stats.init(endpoint.stats()) }
+            Ok(())
+        }).into()
+    }
+
+    /// Enables or disables reporting the statistics of all connections of an endpoint at an interval.
+    ///
+    /// * `handle`: Valid `EndpointHandle` pointer for the duration of the function call.
+    /// * `interval_ms`: The interval in milliseconds, 0 disables reporting.
+    ///
+    /// The statistics of all connections are passed in a single `on_connection_stats` call,
+    /// made by the polling thread or, without `auto-poll`, by the first `poll_endpoint` after the interval elapsed.
+    /// Connections locked by the host at that moment are left out of the report.
+    fn set_connection_stats_interval(handle: EndpointHandle, interval_ms: u64) -> FFIResult {
+        handle.mut_access(&mut |endpoint| {
+            let interval = (interval_ms != 0).then(|| Duration::from_millis(interval_ms));
+            endpoint.set_stats_interval(interval);
+            Ok(())
+        }).into()
+    }
+
+    /// Writes a qlog trace per connection of an endpoint to a directory.
+    ///
+    /// * `handle`: Valid `EndpointHandle` pointer for the duration of the function call.
+    /// * `path`: A pointer to a utf8 byte buffer storing the path to the directory, it is created if it does not exist.
+    /// * `path_length`: The length of `path`.
+    ///
+    /// Traces use the JSON-SEQ qlog format and are named `endpoint<id>-connection<id>-<client|server>-<start>-<sequence>.sqlog`,
+    /// with the start of the trace in milliseconds since the Unix epoch. Existing files are never overwritten.
+    /// Only connections created after this call are traced.
+    fn set_qlog_directory(handle: EndpointHandle, path: Ref<u8>, path_length: u32) -> FFIResult {
+        let path_bytes = unsafe { path.as_bytes(path_length as usize) };
+
+        handle.mut_access(&mut |endpoint| {
+            let path = std::str::from_utf8(path_bytes)
+                .map_err(|_| FFIErrorKind::invalid_argument("Qlog path not in utf8 format"))?;
+            std::fs::create_dir_all(path)?;
+            endpoint.set_qlog(Some(QlogSink::Directory(PathBuf::from(path))));
+            Ok(())
+        }).into()
+    }
+
+    /// Enables or disables passing the qlog records of all connections of an endpoint to the `on_qlog` callback.
+    ///
+    /// * `handle`: Valid `EndpointHandle` pointer for the duration of the function call.
+    /// * `enabled`: Whether records are passed to the callback, disabling also stops writing traces to a directory.
+    ///
+    /// Each invocation receives one JSON-SEQ record, starting with the record separator and ending with a line feed.
+    /// Only connections created after this call are affected.
+    fn set_qlog_callback(handle: EndpointHandle, enabled: bool) -> FFIResult {
+        handle.mut_access(&mut |endpoint| {
+            endpoint.set_qlog(enabled.then_some(QlogSink::Callback));
+            Ok(())
+        }).into()
+    }
+
      /// Polls the endpoint.
     fn poll_endpoint(handle: EndpointHandle) -> FFIResult {
         handle.mut_access(&mut |endpoint| {
@@ -259,11 +355,29 @@ ffi! {
         let reason_bytes = unsafe { reason.as_bytes(reason_lenght as usize) };
 
        handle.mut_access(&mut |connection| {
-            connection.close(VarInt::from_u64(error_code).unwrap(), reason_bytes);
+            connection.close(VarInt::from_u64(error_code)?, reason_bytes);
             Ok(())
        }).into()
     }
 
+    /// Closes the connection once the peer acknowledged all data of the streams finished with `finish_stream`.
+    ///
+    /// * `handle`: Valid `ConnectionHandle` pointer for the duration of the function call.
+    /// * `reason`: A pointer to a byte buffer with the reason that is sent to the peer.
+    /// * `reason_length`: The length of `reason`.
+    /// * `error_code`: The error code that is sent to the peer.
+    ///
+    /// Closes immediately if no finished stream is waiting for acknowledgement.
+    /// Streams that are stopped or reset before being acknowledged no longer delay the close.
+    fn close_connection_gracefully(handle: ConnectionHandle, reason: Ref<u8>, reason_length: u32, error_code: u64) -> FFIResult {
+        let reason_bytes = unsafe { reason.as_bytes(reason_length as usize) };
+
+        handle.mut_access(&mut |connection| {
+            connection.close_gracefully(VarInt::from_u64(error_code)?, reason_bytes);
+            connection.mark_pollable()
+        }).into()
+    }
+
     /// Frees the connection memory.
     ///
     /// - Make sure this handle is valid for the duration of the call.
@@ -288,14 +402,92 @@ ffi! {
         }
     }
 
-    /// Frees the connection memory.
+    /// Retrieves the negotiated ALPN protocol and the SNI server name of a connection.
     ///
-    /// - Make sure this handle is valid for the duration of the call.
-    /// - Make sure this handle will not be used after this call.
+    /// * `handle`: Valid `ConnectionHandle` pointer for the duration of the function call.
+    /// * `protocol_buf`: Allocated memory for the negotiated protocol.
+    /// * `protocol_buf_len`: The size of the allocated memory buffer `protocol_buf`.
+    /// * `actual_protocol_len`: Allocated memory for the length of the negotiated protocol, zero if ALPN is not used.
+    /// * `server_name_buf`: Allocated memory for the server name.
+    /// * `server_name_buf_len`: The size of the allocated memory buffer `server_name_buf`.
+    /// * `actual_server_name_len`: Allocated memory for the length of the server name, always zero for outgoing connections.
+    ///
+    /// The actual lengths could be used to resize the buffers if result returns `BufferToSmall`.
+    /// Fails if the handshake data is not yet available, see the `on_handshake_data_ready` callback.
+    fn connection_handshake_data(handle: ConnectionHandle, protocol_buf: Out<u8>, protocol_buf_len: size_t, actual_protocol_len: Out<size_t>, server_name_buf: Out<u8>, server_name_buf_len: size_t, actual_server_name_len: Out<size_t>) -> FFIResult {
+        handle.ref_access(&mut |connection| {
+            let data = connection
+                .handshake_data()
+                .ok_or_else(|| FFIErrorKind::error(ErrorCategory::Tls, "Handshake data is not yet available"))?;
+
+            let protocol = data.protocol.unwrap_or_default();
+            let server_name = data.server_name.unwrap_or_default();
+
+            unsafe {
+                actual_protocol_len.init(protocol.len());
+                actual_server_name_len.init(server_name.len());
+            }
+
+            if protocol_buf_len < protocol.len() || server_name_buf_len < server_name.len() {
+                return Err(FFIErrorKind::FFIResultKind(FFIResultKind::BufferToSmall));
+            }
+
+            unsafe {
+                protocol_buf.init_bytes(&protocol);
+                server_name_buf.init_bytes(server_name.as_bytes());
+            }
+
+            Ok(())
+        }).into()
+    }
+
+    /// Retrieves the state of the 0-RTT attempt of an outgoing connection.
+    ///
+    /// * `handle`: Valid `ConnectionHandle` pointer for the duration of the function call.
+    /// * `out_state`: Allocated memory for the `ZeroRttState`.
+    ///
+    /// While the state is `Pending`, data written to streams is sent as 0-RTT data.
+    /// Once the state is `Rejected` that data is not retransmitted, the streams have to be opened again and the data resent.
+    fn connection_zero_rtt_state(handle: ConnectionHandle, out_state: Out<ZeroRttState>) -> FFIResult {
+        handle.ref_access(&mut |connection| {
+            unsafe { out_state.init(connection.zero_rtt_state()) }
+            Ok(())
+        }).into()
+    }
+
+    /// Retrieves the statistics of a connection.
+    ///
+    /// * `handle`: Valid `ConnectionHandle` pointer for the duration of the function call.
+    /// * `out_stats`: Allocated memory for the connection statistics.
+    fn connection_stats(handle: ConnectionHandle, out_stats: Out<ConnectionStats>) -> FFIResult {
+        handle.ref_access(&mut |connection| {
+            unsafe { out_stats.init(connection.stats()) }
+            Ok(())
+        }).into()
+    }
+
+    /// Retrieves whether this side of the connection is the client or the server.
+    ///
+    /// * `handle`: Valid `ConnectionHandle` pointer for the duration of the function call.
+    /// * `out_side`: Allocated memory for the side of the connection.
+    fn connection_side(handle: ConnectionHandle, out_side: Out<ConnectionSide>) -> FFIResult {
+        handle.ref_access(&mut |connection| {
+            unsafe { out_side.init(connection.side()) }
+            Ok(())
+        }).into()
+    }
+
+    /// Finishes the send side of a stream.
+    ///
+    /// * `handle`: Valid `ConnectionHandle` pointer for the duration of the function call.
+    /// * `stream_id`: The id of the stream to finish.
+    ///
+    /// Bytes buffered by `write_stream_all` are sent before the stream is finished.
+    /// `on_stream_acked` is invoked once the peer acknowledged all data of the stream.
     fn finish_stream(handle: ConnectionHandle, stream_id: u64) -> FFIResult {
        handle.mut_access(&mut |connection| {
-            connection.inner.send_stream(StreamId(stream_id)).finish();
-            Ok(())
+            connection.finish_stream(StreamId(stream_id))?;
+            connection.mark_pollable()
        }).into()
     }
 }
@@ -308,6 +500,7 @@ ffi! {
     /// * `actual_error_buf_len`: Allocated memory for the actual length of the error buffer.
     ///
     /// `actual_error_buf_len` could be used to resize buffer if result returns `BufferToSmall`.
+    /// The error stays available until another FFI call on the same thread fails.
    fn last_error(error_buf: Out<u8>, error_buf_len: size_t, actual_error_buf_len: Out<size_t>) -> FFIResult {
         FFIResult::from_last_result(|last_result| {
             if let Some(error_msg) = last_result {
@@ -322,7 +515,7 @@ ffi! {
                 }
 
                 if error_buf_len < error_as_bytes.len() {
-                    return FFIResult::buffer_too_small();
+                    return FFIResult::buffer_too_small().keep_last_result();
                 }
 
                 // "The buffer is valid for writes and the length is within the buffer"
@@ -333,6 +526,49 @@ ffi! {
             FFIResult::ok()
         })
     }
+
+    /// Retrieves the category, error code and message of the last occurred error.
+    ///
+    /// * `out_info`: Allocated memory for the `ErrorInfo`.
+    ///
+    /// The category is `None` if no error occurred on this thread.
+    /// The message is owned by the library and stays valid until another FFI call on the same thread fails.
+    fn last_error_info(out_info: Out<ErrorInfo>) -> FFIResult {
+        unsafe {
+            out_info.init(FFIResult::last_error_info());
+        }
+        FFIResult::ok()
+    }
+
+    /// Renders the metrics of all endpoints and their connections in the Prometheus text exposition format.
+    ///
+    /// * `metrics_buf`: Allocated memory for the utf8 encoded metrics.
+    /// * `metrics_buf_len`: The size of the allocated memory buffer `metrics_buf`.
+    /// * `actual_metrics_len`: Allocated memory for the length of the metrics.
+    ///
+    /// Metric names are prefixed with `quinn_endpoint_` or `quinn_connection_` and labeled with `endpoint`,
+    /// connection metrics also with `connection`. Endpoints locked at the moment of rendering are rendered with their last known
+    /// metrics, connections locked at that moment are left out.
+    /// `actual_metrics_len` could be used to resize the buffer if result returns `BufferToSmall`,
+    /// retrying on the same thread within a second with a buffer of that size returns the metrics that did not fit.
+    fn render_prometheus_metrics(metrics_buf: Out<u8>, metrics_buf_len: size_t, actual_metrics_len: Out<size_t>) -> FFIResult {
+        let metrics = take_pending_or_render_prometheus(metrics_buf_len);
+
+        unsafe {
+            actual_metrics_len.init(metrics.len());
+        }
+
+        if metrics_buf_len < metrics.len() {
+            store_pending_metrics(metrics);
+            return FFIResult::buffer_too_small();
+        }
+
+        unsafe {
+            metrics_buf.init_bytes(metrics.as_bytes());
+        }
+
+        FFIResult::ok()
+    }
 }
 
 ffi! {
@@ -345,13 +581,14 @@ ffi! {
         let dir = dir_from_u8(stream_direction);
         handle.mut_access(&mut |connection| {
            let result = if let Some(stream_id) = connection.inner.streams().accept(dir) {
+                connection.register_stream(stream_id);
                 connection.mark_pollable()?;
                 unsafe {
                     stream_id_out.init(VarInt::from(stream_id).into());
                 }
                 Ok(())
             } else {
-                Err(FFIErrorKind::io_error("No stream to accept!"))
+                Err(FFIErrorKind::error(ErrorCategory::StreamLimit, "No stream to accept!"))
             };
 
             result
@@ -373,10 +610,67 @@ ffi! {
             _read_stream(
                 connection,
                 stream_id,
+                true,
                 &mut message_buf,
                 message_buf_len,
                 &mut actual_message_len,
-            )
+            )?;
+            Ok(())
+        }).into()
+    }
+
+    /// Reads from a stream without ordering guarantees.
+    ///
+    /// * `handle`: Valid `ConnectionHandle` pointer for the duration of the function call.
+    /// * `stream_id`: The id of the stream to read from.
+    /// * `message_buf`: Allocated memory for the buffer destination.
+    /// * `message_buf_len`: The size of the allocated memory buffer `message_buf`, chunks larger than this are split.
+    /// * `actual_message_len`: Allocated memory for number of bytes read.
+    /// * `out_offset`: Allocated memory for the stream offset of the bytes read.
+    ///
+    /// Chunks are returned as soon as they arrive which avoids head-of-line blocking, use `out_offset` to reassemble the data.
+    /// Once a stream is read unordered it can no longer be read with `read_stream`.
+    fn read_stream_unordered(handle: ConnectionHandle, stream_id: u64, message_buf: Out<u8>, message_buf_len: size_t, actual_message_len: Out<size_t>, out_offset: Out<u64>) -> FFIResult {
+        handle.mut_access(&mut |connection| {
+            let offset = _read_stream(
+                connection,
+                stream_id,
+                false,
+                &mut message_buf,
+                message_buf_len,
+                &mut actual_message_len,
+            )?;
+
+            if let Some(offset) = offset {
+                unsafe { out_offset.init(offset) }
+            }
+
+            Ok(())
+        }).into()
+    }
+
+    /// Reads as many consecutive chunks from a stream as fit in the buffer.
+    ///
+    /// * `handle`: Valid `ConnectionHandle` pointer for the duration of the function call.
+    /// * `stream_id`: The id of the stream to read from.
+    /// * `message_buf`: Allocated memory for the buffer destination.
+    /// * `message_buf_len`: The size of the allocated memory buffer `message_buf`.
+    /// * `actual_message_len`: Allocated memory for the total number of bytes read.
+    /// * `out_status`: Allocated memory for the `StreamReadStatus`, indicating whether more data or the end of the stream is pending.
+    ///
+    /// Returns `BufferBlocked` if no data is available.
+    /// Unlike `read_stream`, this drains a busy stream with a single call and marks the connection pollable at most once.
+    fn read_stream_batch(handle: ConnectionHandle, stream_id: u64, message_buf: Out<u8>, message_buf_len: size_t, actual_message_len: Out<size_t>, out_status: Out<StreamReadStatus>) -> FFIResult {
+        handle.mut_access(&mut |connection| {
+            let buffer = unsafe { message_buf.as_uninit_bytes_mut(message_buf_len) };
+            let (read, status) = connection.read_stream_chunks(_stream_id(stream_id)?, buffer)?;
+
+            unsafe {
+                actual_message_len.init(read);
+                out_status.init(status);
+            }
+
+            Ok(())
         }).into()
     }
 
@@ -387,9 +681,127 @@ ffi! {
     /// * `buffer`: Allocated and initialized memory for the buffer that is written.
     /// * `buf_len`: Length of the allocated and initialized memory buffer `buffer`.
     /// * `written_bytes`: Allocated memory for the number of bytes written.
+    ///
+    /// Returns `WouldBlock` if flow control does not allow any bytes to be written, retry when the stream becomes writable.
+    /// Also returns `WouldBlock` while bytes written with `write_stream_all` are buffered for the stream.
     fn write_stream(handle: ConnectionHandle, stream_id: u64, buffer: Ref<u8>, buf_len: size_t, written_bytes: Out<size_t>) -> FFIResult {
+        let bytes = unsafe { buffer.as_bytes(buf_len) };
+
+        handle.mut_access(&mut move |connection| {
+            _write_stream(connection, stream_id, &[bytes], &mut written_bytes)
+        }).into()
+    }
+
+    /// Writes multiple buffers to a stream in order, as if they were a single concatenated buffer.
+    ///
+    /// * `handle`: Valid `ConnectionHandle` pointer for the duration of the function call.
+    /// * `stream_id`: The id of the stream to write to.
+    /// * `buffers`: Allocated and initialized memory for an array of buffers that are written.
+    /// * `buffers_len`: The number of buffers in `buffers`.
+    /// * `written_bytes`: Allocated memory for the total number of bytes written.
+    ///
+    /// Like `write_stream`, the buffers can be written partially.
+    fn write_stream_vectored(handle: ConnectionHandle, stream_id: u64, buffers: Ref<StreamBuffer>, buffers_len: size_t, written_bytes: Out<size_t>) -> FFIResult {
+        let slices = unsafe { buffers.as_slice(buffers_len).iter().map(|buffer| buffer.as_bytes()).collect::<Vec<_>>() };
+
         handle.mut_access(&mut move |connection| {
-            _write_stream(connection, stream_id, &mut buffer, buf_len, &mut written_bytes).into()
+            _write_stream(connection, stream_id, &slices, &mut written_bytes)
+        }).into()
+    }
+
+    /// Allocates a buffer that is leased to the host, to be written with `write_stream_leased` without being copied.
+    ///
+    /// * `len`: The length of the buffer.
+    /// * `out_buffer`: Allocated memory for the leased buffer.
+    ///
+    /// A leased buffer that is not written must be returned with `release_stream_buffer`.
+    fn lease_stream_buffer(len: size_t, out_buffer: Out<StreamBuffer>) -> FFIResult {
+        unsafe {
+            out_buffer.init(StreamBuffer::lease(len));
+        }
+
+        FFIResult::ok()
+    }
+
+    /// Frees a buffer returned by `lease_stream_buffer`.
+    ///
+    /// * `buffer`: The leased buffer with its length unchanged.
+    fn release_stream_buffer(buffer: StreamBuffer) -> FFIResult {
+        drop(unsafe { buffer.into_bytes() });
+
+        FFIResult::ok()
+    }
+
+    /// Writes all leased buffers to a stream like `write_stream_all`, taking ownership of the buffers instead of copying them.
+    ///
+    /// * `handle`: Valid `ConnectionHandle` pointer for the duration of the function call.
+    /// * `stream_id`: The id of the stream to write to.
+    /// * `buffers`: Allocated and initialized memory for an array of buffers returned by `lease_stream_buffer`.
+    /// * `buffers_len`: The number of buffers in `buffers`.
+    ///
+    /// The buffers are only taken once the arguments are validated and the write buffer limit allows them:
+    /// if `ArgumentNull`, `HandlePoisoned`, `InvalidArgument` or `WouldBlock` is returned the buffers stay leased to the host
+    /// and must be retried or released with `release_stream_buffer`.
+    /// On any other result, including errors writing to the stream, the buffers must not be used after the call.
+    fn write_stream_leased(handle: ConnectionHandle, stream_id: u64, buffers: Ref<StreamBuffer>, buffers_len: size_t) -> FFIResult {
+        let buffers = unsafe { buffers.as_slice(buffers_len) };
+        let len = buffers.iter().map(|buffer| buffer.len).sum();
+
+        handle.mut_access(&mut |connection| {
+            let stream_id = _stream_id(stream_id)?;
+            connection.check_write_buffer_limit(len)?;
+
+            let chunks = buffers.iter().map(|buffer| unsafe { buffer.into_bytes() }).collect();
+            connection.write_all_chunks(stream_id, chunks)?;
+            connection.mark_pollable()
+        }).into()
+    }
+
+    /// Writes all bytes to a stream, buffering the bytes that can not be sent yet.
+    ///
+    /// * `handle`: Valid `ConnectionHandle` pointer for the duration of the function call.
+    /// * `stream_id`: The id of the stream to write to.
+    /// * `buffer`: Allocated and initialized memory for the buffer that is written.
+    /// * `buf_len`: Length of the allocated and initialized memory buffer `buffer`.
+    ///
+    /// Buffered bytes are sent as soon as flow control allows, `on_stream_drained` is invoked once all of them are sent.
+    /// While bytes are buffered for a stream, `on_stream_writable` is not invoked for it.
+    /// Returns `WouldBlock` without writing anything if the bytes would exceed the write buffer limit of the connection,
+    /// see `set_write_buffer_limit`.
+    fn write_stream_all(handle: ConnectionHandle, stream_id: u64, buffer: Ref<u8>, buf_len: size_t) -> FFIResult {
+        let bytes = unsafe { buffer.as_bytes(buf_len) };
+
+        handle.mut_access(&mut |connection| {
+            connection.write_all(_stream_id(stream_id)?, bytes)?;
+            connection.mark_pollable()
+        }).into()
+    }
+
+    /// Sets the maximum number of bytes a connection buffers for `write_stream_all` across all its streams.
+    ///
+    /// * `handle`: Valid `ConnectionHandle` pointer for the duration of the function call.
+    /// * `limit`: The maximum number of buffered bytes, defaults to 1 MiB.
+    fn set_write_buffer_limit(handle: ConnectionHandle, limit: u64) -> FFIResult {
+        handle.mut_access(&mut |connection| {
+            connection.set_write_buffer_limit(limit as usize);
+            Ok(())
+        }).into()
+    }
+
+    /// Retrieves the statistics of a stream.
+    ///
+    /// * `handle`: Valid `ConnectionHandle` pointer for the duration of the function call.
+    /// * `stream_id`: The id of the stream.
+    /// * `out_stats`: Allocated memory for the stream statistics.
+    ///
+    /// Statistics are available from when the stream is opened or accepted. Once both sides of the stream are closed,
+    /// its final statistics are kept among those of the last 64 closed streams, afterwards an error is returned.
+    /// Quinn does not expose the flow control credit of a stream, `write_blocked` tells whether the credit is exhausted.
+    fn stream_stats(handle: ConnectionHandle, stream_id: u64, out_stats: Out<StreamStats>) -> FFIResult {
+        handle.ref_access(&mut |connection| {
+            let stats = connection.stream_stats(_stream_id(stream_id)?)?;
+            unsafe { out_stats.init(stats) }
+            Ok(())
         }).into()
     }
 
@@ -403,33 +815,122 @@ ffi! {
            let opened_stream = connection.inner.streams().open(dir_from_u8(stream_direction));
 
             if let Some(stream_id) = opened_stream {
+                connection.register_stream(stream_id);
                 unsafe { opened_stream_id.init(_stream_id_to_u64(stream_id)) }
                 Ok(())
             } else {
-                Err(FFIErrorKind::io_error("Streams in the given direction are currently exhausted"))
+                Err(FFIErrorKind::error(ErrorCategory::StreamLimit, "Streams in the given direction are currently exhausted"))
             }
         }).into()
     }
+
+    /// Requests a stream with a certain directionality to be opened as soon as the stream limit allows.
+    ///
+    /// * `handle`: Valid `ConnectionHandle` pointer for the duration of the function call.
+    /// * `stream_direction`: The direction of the stream that is opened.
+    /// * `token`: A nonzero token chosen by the host that is passed to `on_stream_opened` with the opened stream.
+    ///
+    /// Requests are served in order when the connection is polled.
+    /// Streams opened by the peer are reported by `on_stream_opened` with token 0 and `locally_opened` unset,
+    /// passing token 0 returns `InvalidArgument`.
+    /// Requests that are still pending when the connection is lost are reported by `on_stream_open_failed` with their token.
+    fn open_stream_deferred(handle: ConnectionHandle, stream_direction: u8, token: u64) -> FFIResult {
+        handle.mut_access(&mut |connection| {
+            connection.open_stream_deferred(dir_from_u8(stream_direction), token)?;
+            connection.mark_pollable()
+        }).into()
+    }
 }
 
 ffi! {
-    /// Enables a global logger with the given log filter.
-    /// This function may be called only once.
+    /// Enables or disables pushing stream data to the client application.
+    ///
+    /// * `handle`: Valid `ConnectionHandle` pointer for the duration of the function call.
+    /// * `enabled`: Whether stream data is pushed.
+    ///
+    /// When enabled, readable streams are read after each connection poll and the data is delivered with the `on_stream_data` callback,
+    /// `on_stream_readable` is no longer invoked. Each chunk is delivered with status `Readable`,
+    /// a final invocation without data reports the `Finished` or `Reset` status.
+    /// The data pointer is only valid for the duration of the callback.
+    ///
+    /// Enable push mode before the peer sends stream data, streams that are already readable are not pushed until new data arrives.
+    /// Set the `on_stream_data` callback first, enabling push mode without it returns `InvalidArgument`.
+    /// Streams that became readable but were not yet pushed when push mode is disabled are reported with `on_stream_readable`.
+    fn set_stream_push_mode(handle: ConnectionHandle, enabled: bool) -> FFIResult {
+        handle.mut_access(&mut |connection| {
+            connection.set_push_stream_data(enabled)
+        }).into()
+    }
+
+    /// Sets the send priority of a stream.
+    ///
+    /// * `handle`: Valid `ConnectionHandle` pointer for the duration of the function call.
+    /// * `stream_id`: The id of the stream, the stream must have a send side.
+    /// * `priority`: The priority, data of streams with a higher priority is sent first. Streams start with priority `0`.
+    ///
+    /// Streams with the same priority share the available bandwidth.
+    fn set_stream_priority(handle: ConnectionHandle, stream_id: u64, priority: i32) -> FFIResult {
+        handle.mut_access(&mut |connection| {
+            connection.inner.send_stream(_stream_id(stream_id)?).set_priority(priority)?;
+            Ok(())
+        }).into()
+    }
+
+    /// Retrieves the send priority of a stream.
+    ///
+    /// * `handle`: Valid `ConnectionHandle` pointer for the duration of the function call.
+    /// * `stream_id`: The id of the stream, the stream must have a send side.
+    /// * `out_priority`: Allocated memory for the priority.
+    fn stream_priority(handle: ConnectionHandle, stream_id: u64, out_priority: Out<i32>) -> FFIResult {
+        handle.mut_access(&mut |connection| {
+            let priority = connection.inner.send_stream(_stream_id(stream_id)?).priority()?;
+            unsafe { out_priority.init(priority) }
+            Ok(())
+        }).into()
+    }
+
+    /// Sets the send priority that streams of a direction start with.
+    ///
+    /// * `handle`: Valid `ConnectionHandle` pointer for the duration of the function call.
+    /// * `stream_direction`: The direction of the streams the priority applies to.
+    /// * `priority`: The priority newly opened and accepted streams start with.
+    ///
+    /// Streams that are already open keep their priority.
+    fn set_default_stream_priority(handle: ConnectionHandle, stream_direction: u8, priority: i32) -> FFIResult {
+        handle.mut_access(&mut |connection| {
+            connection.set_default_stream_priority(dir_from_u8(stream_direction), priority);
+            Ok(())
+        }).into()
+    }
+}
+
+ffi! {
+    /// Enables a global logger forwarding log records to the `on_log` callback.
+    ///
+    /// * `log_filter`: A pointer to a utf8 byte buffer storing the filter directives, e.g. `quinn_ffi=debug,quinn_proto=info`.
+    /// * `log_filter_length`: The length of `log_filter`.
+    ///
+    /// Calling this function again replaces the filter. Records are dropped while no `on_log` callback is set.
     #[cfg(feature="debug")]
     fn enable_log(log_filter: Ref<u8>, log_filter_length: u32) -> FFIResult {
         let log_filter_bytes = unsafe { log_filter.as_bytes(log_filter_length as usize) };
-        let log_filter = String::from_utf8(log_filter_bytes.to_vec()).unwrap();
-
-        // TODO: possibly let the user define the subscriber.
-        // TODO: possibly use `set_default` and return a handle containing the log guard.
-        tracing::subscriber::set_global_default(
-        tracing_subscriber::FmtSubscriber::builder()
-            .with_env_filter(&log_filter)
-            .finish(),
-        )
-        .unwrap();
 
-        FFIResult::ok()
+        log_filter_str(log_filter_bytes)
+            .and_then(crate::proto_impl::enable_log)
+            .into()
+    }
+
+    /// Replaces the filter directives of the logger enabled with `enable_log`.
+    ///
+    /// * `log_filter`: A pointer to a utf8 byte buffer storing the filter directives.
+    /// * `log_filter_length`: The length of `log_filter`.
+    #[cfg(feature="debug")]
+    fn set_log_filter(log_filter: Ref<u8>, log_filter_length: u32) -> FFIResult {
+        let log_filter_bytes = unsafe { log_filter.as_bytes(log_filter_length as usize) };
+
+        log_filter_str(log_filter_bytes)
+            .and_then(crate::proto_impl::set_log_filter)
+            .into()
     }
 
     /// Creates and configures a server crypto configuration.
@@ -448,24 +949,100 @@ ffi! {
     /// * only high-quality key exchange groups: curve25519, secp256r1, secp384r1.
     /// * only TLS 1.2 and 1.3 support.
     fn create_server_config(out_handle: Out<RustlsServerConfigHandle>, cert: Ref<u8>, cert_lenght: u32, key: Ref<u8>, key_lenght: u32) -> FFIResult {
-        let (cert, key, _store) = unsafe { decode_cert_key_store(&cert, cert_lenght, &key, key_lenght) };
+        let (cert, key, _store) = match unsafe { decode_cert_key_store(&cert, cert_lenght, &key, key_lenght) } {
+            Ok(decoded) => decoded,
+            Err(e) => return FFIResult::invalid_argument().context(e),
+        };
 
-        let mut crypto = rustls::ServerConfig::builder()
+        let crypto = rustls::ServerConfig::builder()
             .with_safe_default_cipher_suites()
             .with_safe_default_kx_groups()
             .with_safe_default_protocol_versions()
-            .unwrap()
-            .with_no_client_auth()
-            .with_single_cert(vec![cert], key)
-            .expect("bad certificate/key");
+            .map(|builder| builder.with_no_client_auth().with_single_cert(vec![cert], key));
 
-        let config = ServerConfig::with_crypto(Arc::new(crypto));
+        let mut crypto = match crypto {
+            Ok(Ok(crypto)) => crypto,
+            Ok(Err(e)) | Err(e) => return FFIResult::invalid_argument().context(FFIErrorKind::invalid_argument(&format!("Bad certificate or key: {}", e))),
+        };
 
-        unsafe { out_handle.init(RustlsServerConfigHandle::new(ServerConfig::from(config))) }
+        unsafe { out_handle.init(RustlsServerConfigHandle::new(ServerConfigImpl::new(crypto))) }
 
         FFIResult::ok()
     }
 
+    /// Creates a server crypto configuration that selects the certificate by the SNI server name the client sent.
+    ///
+    /// * `out_handle`: Allocated memory for a pointer to a `RustlsServerConfigHandle`.
+    ///
+    /// The configuration starts without certificates, use `set_server_certificate` to add them.
+    /// Certificates can be added, replaced and removed while endpoints created with this configuration are running.
+    /// Keep the handle alive for as long as certificates should be managed.
+    ///
+    /// The default configuration contains the same cipher suites, key exchange groups and protocol versions as `create_server_config`.
+    fn create_sni_server_config(out_handle: Out<RustlsServerConfigHandle>) -> FFIResult {
+        let cert_resolver = Arc::new(SniCertResolver::default());
+
+        let crypto = match rustls::ServerConfig::builder()
+            .with_safe_default_cipher_suites()
+            .with_safe_default_kx_groups()
+            .with_safe_default_protocol_versions()
+        {
+            Ok(builder) => builder.with_no_client_auth().with_cert_resolver(cert_resolver.clone()),
+            Err(e) => return FFIResult::err().context(e.into()),
+        };
+
+        unsafe { out_handle.init(RustlsServerConfigHandle::new(ServerConfigImpl::with_cert_resolver(crypto, cert_resolver))) }
+
+        FFIResult::ok()
+    }
+
+    /// Adds or replaces the certificate presented for a server name.
+    ///
+    /// * `handle`: Valid `RustlsServerConfigHandle` pointer, created with `create_sni_server_config`, for the duration of the function call.
+    /// * `server_name`: A pointer to a utf8 byte buffer storing the server name, an empty name sets the default certificate.
+    /// * `server_name_length`: The length of `server_name`.
+    /// * `cert_chain`: A pointer to one or more concatenated DER-encoded X.509 certificates, leaf certificate first.
+    /// * `cert_chain_length`: The length of `cert_chain`.
+    /// * `key`: A pointer to the private key, DER-encoded ASN.1 in either PKCS#8 or PKCS#1 format.
+    /// * `key_length`: The length of `key`.
+    ///
+    /// Only new handshakes use the certificate, established connections are not affected.
+    fn set_server_certificate(handle: RustlsServerConfigHandle, server_name: Ref<u8>, server_name_length: u32, cert_chain: Ref<u8>, cert_chain_length: u32, key: Ref<u8>, key_length: u32) -> FFIResult {
+        let server_name_bytes = unsafe { server_name.as_bytes(server_name_length as usize) };
+        let cert_chain_bytes = unsafe { cert_chain.as_bytes(cert_chain_length as usize) };
+        let key = PrivateKey(Vec::from(unsafe { key.as_bytes(key_length as usize) }));
+
+        handle.ref_access(&mut |server_config| {
+            let server_name = std::str::from_utf8(server_name_bytes)
+                .map_err(|_| FFIErrorKind::invalid_argument("Server name not in utf8 format"))?;
+            let cert_chain = decode_certificate_chain(cert_chain_bytes)?;
+
+            sni_cert_resolver(server_config)?.set_certificate(server_name, cert_chain, &key)
+        }).into()
+    }
+
+    /// Removes the certificate presented for a server name.
+    ///
+    /// * `handle`: Valid `RustlsServerConfigHandle` pointer, created with `create_sni_server_config`, for the duration of the function call.
+    /// * `server_name`: A pointer to a utf8 byte buffer storing the server name, an empty name removes the default certificate.
+    /// * `server_name_length`: The length of `server_name`.
+    ///
+    /// New handshakes for the server name fail afterwards unless a default certificate is set, established connections are not affected.
+    fn remove_server_certificate(handle: RustlsServerConfigHandle, server_name: Ref<u8>, server_name_length: u32) -> FFIResult {
+        let server_name_bytes = unsafe { server_name.as_bytes(server_name_length as usize) };
+
+        handle.ref_access(&mut |server_config| {
+            let server_name = std::str::from_utf8(server_name_bytes)
+                .map_err(|_| FFIErrorKind::invalid_argument("Server name not in utf8 format"))?;
+
+            if sni_cert_resolver(server_config)?.remove_certificate(server_name) {
+                Ok(())
+            } else {
+                Err(FFIErrorKind::error(ErrorCategory::Tls, "No certificate set for the given server name"))
+            }
+        }).into()
+    }
+
     /// Creates and configures a client crypto configuration.
     ///
     /// * `out_handle`: Allocated memory for a pointer to a `RustlsServerConfigHandle`.
@@ -482,26 +1059,245 @@ ffi! {
     /// * only high-quality key exchange groups: curve25519, secp256r1, secp384r1.
     /// * only TLS 1.2 and 1.3 support.
     fn create_client_config(out_handle: Out<RustlsClientConfigHandle>, cert: Ref<u8>, cert_lenght: u32, key: Ref<u8>, key_lenght: u32) -> FFIResult {
-        let (cert, key, store) = unsafe { decode_cert_key_store(&cert, cert_lenght, &key, key_lenght) };
+        let (cert, key, store) = match unsafe { decode_cert_key_store(&cert, cert_lenght, &key, key_lenght) } {
+            Ok(decoded) => decoded,
+            Err(e) => return FFIResult::invalid_argument().context(e),
+        };
 
-        let mut crypto = rustls::ClientConfig::builder()
+        let crypto = rustls::ClientConfig::builder()
             .with_safe_default_cipher_suites()
             .with_safe_default_kx_groups()
             .with_safe_default_protocol_versions()
-            .unwrap()
-            .with_root_certificates(store)
-            .with_single_cert(vec![cert], key)
-            .expect("bad certificate/key");
+            .map(|builder| builder.with_root_certificates(store).with_single_cert(vec![cert], key));
+
+        let mut crypto = match crypto {
+            Ok(Ok(crypto)) => crypto,
+            Ok(Err(e)) | Err(e) => return FFIResult::invalid_argument().context(FFIErrorKind::invalid_argument(&format!("Bad certificate or key: {}", e))),
+        };
+
+        unsafe {
+            out_handle.init(RustlsClientConfigHandle::new(ClientConfigImpl::new(crypto)));
+        }
+
+        FFIResult::ok()
+    }
+
+    /// Generates a self-signed certificate and private key.
+    ///
+    /// * `subject_alt_names`: A pointer to a utf8 byte buffer storing comma separated DNS names the certificate is valid for.
+    /// * `subject_alt_names_length`: The length of `subject_alt_names`.
+    /// * `out_cert`: Allocated memory for the DER-encoded X.509 certificate.
+    /// * `cert_buf_len`: The size of the allocated memory buffer `out_cert`.
+    /// * `actual_cert_len`: Allocated memory for the length of the certificate.
+    /// * `out_key`: Allocated memory for the DER-encoded private key in PKCS#8 format.
+    /// * `key_buf_len`: The size of the allocated memory buffer `out_key`.
+    /// * `actual_key_len`: Allocated memory for the length of the private key.
+    ///
+    /// The certificate and key can be passed directly to `create_server_config` and `create_client_config`.
+    /// The actual lengths could be used to resize the buffers if result returns `BufferToSmall`,
+    /// retrying on the same thread with the same names returns the certificate that did not fit.
+    // The certificate and the key each take a buffer, its size and the actual length, like the other out-buffer functions.
+    #[allow(clippy::too_many_arguments)]
+    fn generate_self_signed(subject_alt_names: Ref<u8>, subject_alt_names_length: u32, out_cert: Out<u8>, cert_buf_len: size_t, actual_cert_len: Out<size_t>, out_key: Out<u8>, key_buf_len: size_t, actual_key_len: Out<size_t>) -> FFIResult {
+        let subject_alt_names = match std::str::from_utf8(unsafe { subject_alt_names.as_bytes(subject_alt_names_length as usize) }) {
+            Ok(names) => names.split(',').map(|name| name.trim().to_owned()).filter(|name| !name.is_empty()).collect::<Vec<_>>(),
+            Err(_) => return FFIResult::invalid_argument().context(FFIErrorKind::invalid_argument("Subject alternative names not in utf8 format")),
+        };
+
+        let certificate = match SelfSignedCertificate::take_pending_or_generate(subject_alt_names) {
+            Ok(certificate) => certificate,
+            Err(e) => return FFIResult::err().context(e),
+        };
 
-        let config = ClientConfig::new(Arc::new(crypto));
+        unsafe {
+            actual_cert_len.init(certificate.cert.len());
+            actual_key_len.init(certificate.key.len());
+        }
+
+        if cert_buf_len < certificate.cert.len() || key_buf_len < certificate.key.len() {
+            certificate.store_pending();
+            return FFIResult::buffer_too_small();
+        }
 
         unsafe {
-            out_handle.init(RustlsClientConfigHandle::new(config));
+            out_cert.init_bytes(&certificate.cert);
+            out_key.init_bytes(&certificate.key);
         }
 
         FFIResult::ok()
     }
 
+    /// Sets the ALPN protocols the server is willing to negotiate, in order of preference.
+    ///
+    /// * `handle`: Valid `RustlsServerConfigHandle` pointer for the duration of the function call.
+    /// * `protocols`: A pointer to the protocol list, each protocol is prefixed with its length as a single byte.
+    /// * `protocols_length`: The length of `protocols`.
+    ///
+    /// Once set, connections from clients that have no protocol in common with the server are rejected during the handshake.
+    /// Only endpoints created after this call use the new protocols.
+    fn set_server_alpn_protocols(handle: RustlsServerConfigHandle, protocols: Ref<u8>, protocols_length: u32) -> FFIResult {
+        let protocols_bytes = unsafe { protocols.as_bytes(protocols_length as usize) };
+
+        handle.mut_access(&mut |server_config| {
+            let protocols = decode_alpn_protocols(protocols_bytes)?;
+            server_config.update_crypto(|crypto| crypto.alpn_protocols = protocols);
+            Ok(())
+        }).into()
+    }
+
+    /// Sets the ALPN protocols the client offers, in order of preference.
+    ///
+    /// * `handle`: Valid `RustlsClientConfigHandle` pointer for the duration of the function call.
+    /// * `protocols`: A pointer to the protocol list, each protocol is prefixed with its length as a single byte.
+    /// * `protocols_length`: The length of `protocols`.
+    ///
+    /// Only endpoints created after this call use the new protocols.
+    fn set_client_alpn_protocols(handle: RustlsClientConfigHandle, protocols: Ref<u8>, protocols_length: u32) -> FFIResult {
+        let protocols_bytes = unsafe { protocols.as_bytes(protocols_length as usize) };
+
+        handle.mut_access(&mut |client_config| {
+            let protocols = decode_alpn_protocols(protocols_bytes)?;
+            client_config.update_crypto(|crypto| crypto.alpn_protocols = protocols);
+            Ok(())
+        }).into()
+    }
+
+    /// Enables or disables sending 0-RTT data when resuming a session.
+    ///
+    /// * `handle`: Valid `RustlsClientConfigHandle` pointer for the duration of the function call.
+    /// * `enabled`: Whether 0-RTT data is sent.
+    ///
+    /// 0-RTT data can be replayed by an attacker, only send idempotent requests as 0-RTT data.
+    /// Only endpoints created after this call use the new setting.
+    fn set_client_early_data(handle: RustlsClientConfigHandle, enabled: bool) -> FFIResult {
+        handle.mut_access(&mut |client_config| {
+            client_config.update_crypto(|crypto| crypto.enable_early_data = enabled);
+            Ok(())
+        }).into()
+    }
+
+    /// Serializes the resumption tickets cached by a client configuration.
+    ///
+    /// * `handle`: Valid `RustlsClientConfigHandle` pointer for the duration of the function call.
+    /// * `sessions_buf`: Allocated memory for the serialized sessions.
+    /// * `sessions_buf_len`: The size of the allocated memory buffer `sessions_buf`.
+    /// * `actual_sessions_len`: Allocated memory for the length of the serialized sessions.
+    ///
+    /// `actual_sessions_len` could be used to resize buffer if result returns `BufferToSmall`.
+    /// The serialized sessions contain secrets, store them as securely as a private key.
+    fn export_client_sessions(handle: RustlsClientConfigHandle, sessions_buf: Out<u8>, sessions_buf_len: size_t, actual_sessions_len: Out<size_t>) -> FFIResult {
+        handle.ref_access(&mut |client_config| {
+            let sessions = client_config.session_cache().serialize();
+
+            unsafe { actual_sessions_len.init(sessions.len()) }
+
+            if sessions_buf_len < sessions.len() {
+                return Err(FFIErrorKind::FFIResultKind(FFIResultKind::BufferToSmall));
+            }
+
+            unsafe { sessions_buf.init_bytes(&sessions) }
+
+            Ok(())
+        }).into()
+    }
+
+    /// Restores resumption tickets serialized by `export_client_sessions`.
+    ///
+    /// * `handle`: Valid `RustlsClientConfigHandle` pointer for the duration of the function call.
+    /// * `sessions`: Pointer to the serialized sessions.
+    /// * `sessions_length`: The length of `sessions`.
+    fn import_client_sessions(handle: RustlsClientConfigHandle, sessions: Ref<u8>, sessions_length: size_t) -> FFIResult {
+        let sessions_bytes = unsafe { sessions.as_bytes(sessions_length) };
+
+        handle.ref_access(&mut |client_config| {
+            client_config.session_cache().deserialize(sessions_bytes)
+        }).into()
+    }
+
+    /// Enables or disables accepting 0-RTT data from resuming clients.
+    ///
+    /// * `handle`: Valid `RustlsServerConfigHandle` pointer for the duration of the function call.
+    /// * `max_early_data_size`: `0` disables 0-RTT, QUIC requires `0xffffffff` to enable it since stream flow control limits the amount of early data.
+    ///
+    /// 0-RTT data can be replayed by an attacker, only accept idempotent requests as 0-RTT data.
+    /// Only endpoints created after this call use the new setting.
+    fn set_server_early_data(handle: RustlsServerConfigHandle, max_early_data_size: u32) -> FFIResult {
+        if max_early_data_size != 0 && max_early_data_size != u32::MAX {
+            return FFIResult::err().context(FFIErrorKind::error(ErrorCategory::Config, "QUIC requires max_early_data_size to be 0 or 0xffffffff"));
+        }
+
+        handle.mut_access(&mut |server_config| {
+            server_config.update_crypto(|crypto| crypto.max_early_data_size = max_early_data_size);
+            Ok(())
+        }).into()
+    }
+
+    /// Writes the TLS secrets of connections using this server configuration to a key log file.
+    ///
+    /// * `handle`: Valid `RustlsServerConfigHandle` pointer for the duration of the function call.
+    /// * `path`: A pointer to a utf8 byte buffer storing the path to the key log file.
+    /// * `path_length`: The length of `path`.
+    ///
+    /// The file uses the NSS key log format, like `SSLKEYLOGFILE`, and can be used by Wireshark to decrypt captured packets.
+    /// Only endpoints created after this call log their secrets.
+    #[cfg(feature="key-log")]
+    fn set_server_key_log_file(handle: RustlsServerConfigHandle, path: Ref<u8>, path_length: u32) -> FFIResult {
+        let path_bytes = unsafe { path.as_bytes(path_length as usize) };
+
+        handle.mut_access(&mut |server_config| {
+            let key_log = Arc::new(FileKeyLog::open(key_log_path(path_bytes)?)?);
+            server_config.update_crypto(|crypto| crypto.key_log = key_log);
+            Ok(())
+        }).into()
+    }
+
+    /// Forwards the TLS secrets of connections using this server configuration to the `on_key_log` callback.
+    ///
+    /// * `handle`: Valid `RustlsServerConfigHandle` pointer for the duration of the function call.
+    ///
+    /// Each invocation receives one line in the NSS key log format.
+    /// Only endpoints created after this call log their secrets.
+    #[cfg(feature="key-log")]
+    fn set_server_key_log_callback(handle: RustlsServerConfigHandle) -> FFIResult {
+        handle.mut_access(&mut |server_config| {
+            server_config.update_crypto(|crypto| crypto.key_log = Arc::new(CallbackKeyLog));
+            Ok(())
+        }).into()
+    }
+
+    /// Writes the TLS secrets of connections using this client configuration to a key log file.
+    ///
+    /// * `handle`: Valid `RustlsClientConfigHandle` pointer for the duration of the function call.
+    /// * `path`: A pointer to a utf8 byte buffer storing the path to the key log file.
+    /// * `path_length`: The length of `path`.
+    ///
+    /// The file uses the NSS key log format, like `SSLKEYLOGFILE`, and can be used by Wireshark to decrypt captured packets.
+    /// Only endpoints created after this call log their secrets.
+    #[cfg(feature="key-log")]
+    fn set_client_key_log_file(handle: RustlsClientConfigHandle, path: Ref<u8>, path_length: u32) -> FFIResult {
+        let path_bytes = unsafe { path.as_bytes(path_length as usize) };
+
+        handle.mut_access(&mut |client_config| {
+            let key_log = Arc::new(FileKeyLog::open(key_log_path(path_bytes)?)?);
+            client_config.update_crypto(|crypto| crypto.key_log = key_log);
+            Ok(())
+        }).into()
+    }
+
+    /// Forwards the TLS secrets of connections using this client configuration to the `on_key_log` callback.
+    ///
+    /// * `handle`: Valid `RustlsClientConfigHandle` pointer for the duration of the function call.
+    ///
+    /// Each invocation receives one line in the NSS key log format.
+    /// Only endpoints created after this call log their secrets.
+    #[cfg(feature="key-log")]
+    fn set_client_key_log_callback(handle: RustlsClientConfigHandle) -> FFIResult {
+        handle.mut_access(&mut |client_config| {
+            client_config.update_crypto(|crypto| crypto.key_log = Arc::new(CallbackKeyLog));
+            Ok(())
+        }).into()
+    }
+
     fn free_client_config(handle: RustlsClientConfigHandle) -> FFIResult {
         unsafe { RustlsClientConfigHandle::dealloc(handle, |_e| {})};
         FFIResult::ok()
@@ -513,40 +1309,76 @@ ffi! {
     }
 }
 
+#[cfg(feature = "debug")]
+fn log_filter_str(log_filter_bytes: &[u8]) -> Result<&str, FFIErrorKind> {
+    std::str::from_utf8(log_filter_bytes)
+        .map_err(|_| FFIErrorKind::invalid_argument("Log filter not in utf8 format"))
+}
+
+#[cfg(feature = "key-log")]
+fn key_log_path(path_bytes: &[u8]) -> Result<&str, FFIErrorKind> {
+    std::str::from_utf8(path_bytes)
+        .map_err(|_| FFIErrorKind::invalid_argument("Key log path not in utf8 format"))
+}
+
+fn sni_cert_resolver(server_config: &ServerConfigImpl) -> Result<&SniCertResolver, FFIErrorKind> {
+    server_config.cert_resolver().ok_or_else(|| {
+        FFIErrorKind::error(
+            ErrorCategory::Config,
+            "Server config does not use SNI based certificate selection",
+        )
+    })
+}
+
 unsafe fn decode_cert_key_store(
     cert: &Ref<u8>,
     cert_length: u32,
     key: &Ref<u8>,
     key_length: u32,
-) -> (Certificate, PrivateKey, RootCertStore) {
+) -> Result<(Certificate, PrivateKey, RootCertStore), FFIErrorKind> {
     let (key, cert) = (
         PrivateKey(Vec::from(key.as_bytes(key_length as usize))),
         Certificate(Vec::from(cert.as_bytes(cert_length as usize))),
     );
     let mut store = RootCertStore::empty();
-    store.add(&cert).unwrap();
+    store
+        .add(&cert)
+        .map_err(|e| FFIErrorKind::invalid_argument(&format!("Bad certificate: {}", e)))?;
 
-    (cert, key, store)
+    Ok((cert, key, store))
 }
 
+/// Reads a single chunk from a stream, returns the stream offset of the chunk if one was read.
 fn _read_stream(
     handle: &mut ConnectionImpl,
     stream_id: u64,
+    ordered: bool,
     message_buf: &mut Out<u8>,
     message_buf_len: size_t,
     actual_message_len: &mut Out<size_t>,
-) -> Result<(), FFIErrorKind> {
-    let mut stream = handle.inner.recv_stream(_stream_id(stream_id)?);
+) -> Result<Option<u64>, FFIErrorKind> {
+    let stream_id = _stream_id(stream_id)?;
+    let mut stream = handle.inner.recv_stream(stream_id);
+
+    let mut result = stream.read(ordered)?;
 
-    let mut result = stream.read(true)?;
+    let mut offset = None;
+    let mut read = 0;
 
-    match result.next(message_buf_len) {
+    let end = match result.next(message_buf_len) {
         Ok(Some(chunk)) => unsafe {
             let mut buffer = message_buf.as_uninit_bytes_mut(message_buf_len);
 
             let written = buffer.write(&chunk.bytes)?;
 
             actual_message_len.init(written);
+            offset = Some(chunk.offset);
+            read = written;
+            None
+        },
+        Ok(None) => unsafe {
+            actual_message_len.init(0);
+            Some(StreamReadStatus::Finished)
         },
         Err(e) => {
             if result.finalize().should_transmit() {
@@ -557,29 +1389,28 @@ fn _read_stream(
                 return Err(FFIErrorKind::FFIResultKind(FFIResultKind::BufferBlocked));
             }
 
+            handle.record_read(stream_id, 0, Some(StreamReadStatus::Reset));
             return Err(e.into());
         }
-        _ => {}
-    }
+    };
 
     if result.finalize().should_transmit() {
         handle.mark_pollable()?;
     }
 
-    Ok(())
+    handle.record_read(stream_id, read, end);
+
+    Ok(offset)
 }
 
 fn _write_stream(
     handle: &mut ConnectionImpl,
     stream_id: u64,
-    buffer: &mut Ref<u8>,
-    buf_len: size_t,
+    slices: &[&[u8]],
     written_bytes: &mut Out<size_t>,
 ) -> Result<(), FFIErrorKind> {
-    let mut stream = handle.inner.send_stream(_stream_id(stream_id)?);
+    let result = handle.write_vectored(_stream_id(stream_id)?, slices)?;
 
-    let bytes = unsafe { buffer.as_bytes(buf_len) };
-    let result = stream.write(bytes)?;
     unsafe {
         written_bytes.init(result);
     }
@@ -612,14 +1443,15 @@ pub mod callbacks {
             ConnectionHandle,
             FFIResult,
         },
-        proto::{
-            StreamId,
-            Transmit,
+        proto::Transmit,
+        proto_impl::{
+            ConnectionStats,
+            IpAddr,
+            StreamDescriptor,
+            StreamReadStatus,
         },
-        proto_impl::IpAddr,
     };
     use libc::size_t;
-    use quinn_proto::VarInt;
     use tracing::trace;
 
     /// Generates FFI methods to set callbacks and declares the static variable to store that callback.
@@ -673,6 +1505,38 @@ pub mod callbacks {
         };
     }
 
+    /// Generates invoke methods for callbacks the host does not have to set, invoking nothing while unset.
+    #[doc(hidden)]
+    macro_rules! set_optional_invokers {
+        ($(invoke $name:ident with $fn_name:ident ( $( $arg_ident:ident : $arg_ty:ty),* ) )*) => {
+             $(
+                /// Invoke the callback, if set.
+                pub(crate) fn $fn_name($($arg_ident: $arg_ty),*) {
+                    unsafe {
+                       if let Some(callback) = $name {
+                           trace!("Callback Invoke: {} ({})", stringify!($name), stringify!(($($arg_ident),*)));
+                           callback($($arg_ident),*);
+                       }
+                    }
+                }
+              )*
+        };
+
+        // Allows parsing parameters with `call(int as u8)` for example.
+        ($(invoke $name:ident with $fn_name:ident ( $( $arg_ident:ident : $arg_ty:ty),* ) { call ($($body:expr),* ) }) *) => {
+             $(
+                /// Invoke the callback, if set.
+                pub(crate) fn $fn_name($($arg_ident: $arg_ty),*) {
+                    unsafe {
+                       if let Some(callback) = $name {
+                           callback($($body), *);
+                       }
+                    }
+                }
+              )*
+        };
+    }
+
     set_invokers! {
         invoke ON_NEW_CONNECTION with on_new_connection(handle: ConnectionHandle, con: u32, endpoint_id: u32)
 
@@ -684,31 +1548,106 @@ pub mod callbacks {
 
         invoke ON_DATAGRAM_RECEIVED with on_datagram_received(con: u32)
 
-        invoke ON_STREAM_OPENED with on_stream_opened(con: u32, stream_id: u64, dir: u8)
+        invoke ON_STREAM_OPENED with on_stream_opened(con: u32, stream: StreamDescriptor, token: u64)
+
+        invoke ON_STREAM_READABLE with on_stream_readable(con: u32, stream: StreamDescriptor)
+
+        invoke ON_STREAM_WRITABLE with on_stream_writable(con: u32, stream: StreamDescriptor)
+
+        invoke ON_STREAM_FINISHED with on_stream_finished(con: u32, stream: StreamDescriptor)
+
+        invoke ON_STREAM_STOPPED with on_stream_stopped(con: u32, stream: StreamDescriptor)
 
         invoke ON_CONNECTION_POLLABLE with on_connection_pollable(con: u32)
 
     }
 
+    set_optional_invokers! {
+        invoke ON_HANDSHAKE_DATA_READY with on_handshake_data_ready(con: u32, protocol: *const u8, protocol_len: u32, server_name: *const u8, server_name_len: u32)
+
+        invoke ON_ZERO_RTT_RESOLVED with on_zero_rtt_resolved(con: u32, state: u8)
+
+        invoke ON_STREAM_DRAINED with on_stream_drained(con: u32, stream: StreamDescriptor)
+
+        invoke ON_STREAM_ACKED with on_stream_acked(con: u32, stream: StreamDescriptor)
+
+        invoke ON_CONNECTION_STATS with on_connection_stats(endpoint_id: u8, connection_ids: *const u32, stats: *const ConnectionStats, count: u32)
+
+        invoke ON_QLOG with on_qlog(con: u32, record: *const u8, len: u32)
+
+        invoke ON_STREAM_OPEN_FAILED with on_stream_open_failed(con: u32, dir: u8, token: u64)
+    }
+
+    /// Returns whether the host set the `on_stream_data` callback.
+    pub(crate) fn has_on_stream_data() -> bool {
+        let callback = unsafe { ON_STREAM_DATA };
+        callback.is_some()
+    }
+
+    set_optional_invokers! {
+        invoke ON_STREAM_DATA with on_stream_data(con: u32, stream: StreamDescriptor, data: *const u8, len: u32, status: StreamReadStatus) {
+            call (con,stream,data,len,status as u8)
+        }
+    }
+
     set_invokers! {
-        invoke ON_STREAM_READABLE with on_stream_readable(con: u32, stream_id: StreamId) {
-            call (con,VarInt::from(stream_id).into(),stream_id.dir() as u8)
+        invoke ON_TRANSMIT with on_transmit(endpoint_id: u8, transmit: Transmit) {
+            call (endpoint_id,transmit.contents.as_ptr(),transmit.contents.len(),&transmit.destination.into())
         }
+    }
+
+    #[cfg(feature = "key-log")]
+    pub(crate) use self::key_log::on_key_log;
 
-        invoke ON_STREAM_WRITABLE with on_stream_writable(con: u32, stream_id: StreamId) {
-            call (con,VarInt::from(stream_id).into(),stream_id.dir() as u8)
+    #[cfg(feature = "key-log")]
+    mod key_log {
+        use super::*;
+
+        set_optional_invokers! {
+            invoke ON_KEY_LOG with on_key_log(line: *const u8, len: u32)
         }
 
-        invoke ON_STREAM_FINISHED with on_stream_finished(con: u32, stream_id: StreamId) {
-            call (con,VarInt::from(stream_id).into(),stream_id.dir() as u8)
+        set_callbacks! {
+            fn set_on_key_log(*const u8, u32) set ON_KEY_LOG
         }
+    }
+
+    #[cfg(feature = "debug")]
+    pub(crate) use self::log::on_log;
 
-        invoke ON_STREAM_STOPPED with on_stream_stopped(con: u32, stream_id: StreamId) {
-            call (con,VarInt::from(stream_id).into(),stream_id.dir() as u8)
+    #[cfg(feature = "debug")]
+    mod log {
+        use super::*;
+
+        set_callbacks! {
+            fn set_on_log(u8, *const u8, u32, *const u8, u32, *const u8, u32) set ON_LOG
         }
 
-        invoke ON_TRANSMIT with on_transmit(endpoint_id: u8, transmit: Transmit) {
-            call (endpoint_id,transmit.contents.as_ptr(),transmit.contents.len(),&transmit.destination.into())
+        /// Invoke the callback, if set.
+        ///
+        /// Unlike the generated invokers this does not trace the invocation, which would log recursively.
+        pub(crate) fn on_log(
+            level: u8,
+            target: *const u8,
+            target_len: u32,
+            message: *const u8,
+            message_len: u32,
+            spans: *const u8,
+            spans_len: u32,
+        ) {
+            unsafe {
+                if let Some(callback) = ON_LOG {
+                    callback(
+                        level,
+                        target,
+                        target_len,
+                        message,
+                        message_len,
+                        spans,
+                        spans_len,
+                    );
+                }
+            }
         }
     }
 
@@ -717,24 +1656,40 @@ pub mod callbacks {
 
         fn set_on_connected(u32) set ON_CONNECTED
 
+        fn set_on_zero_rtt_resolved(u32, u8) set ON_ZERO_RTT_RESOLVED
+
+        fn set_on_handshake_data_ready(u32, *const u8, u32, *const u8, u32) set ON_HANDSHAKE_DATA_READY
+
         fn set_on_connection_lost(u32, *const u8, u32) set ON_CONNECTION_LOST
 
-        fn set_on_stream_writable(u32, u64, u8) set ON_STREAM_WRITABLE
+        fn set_on_stream_writable(u32, StreamDescriptor) set ON_STREAM_WRITABLE
+
+        fn set_on_stream_readable(u32, StreamDescriptor) set ON_STREAM_READABLE
+
+        fn set_on_stream_drained(u32, StreamDescriptor) set ON_STREAM_DRAINED
+
+        fn set_on_stream_data(u32, StreamDescriptor, *const u8, u32, u8) set ON_STREAM_DATA
 
-        fn set_on_stream_readable(u32, u64, u8) set ON_STREAM_READABLE
+        fn set_on_stream_finished(u32, StreamDescriptor) set ON_STREAM_FINISHED
 
-        fn set_on_stream_finished(u32, u64, u8) set ON_STREAM_FINISHED
+        fn set_on_stream_acked(u32, StreamDescriptor) set ON_STREAM_ACKED
 
-        fn set_on_stream_stopped(u32, u64, u8) set ON_STREAM_STOPPED
+        fn set_on_stream_stopped(u32, StreamDescriptor) set ON_STREAM_STOPPED
 
         fn set_on_stream_available(u32, u8) set ON_STREAM_AVAILABLE
 
         fn set_on_datagram_received(u32) set ON_DATAGRAM_RECEIVED
 
-        fn set_on_stream_opened(u32, u64, u8) set ON_STREAM_OPENED
+        fn set_on_stream_opened(u32, StreamDescriptor, u64) set ON_STREAM_OPENED
+
+        fn set_on_stream_open_failed(u32, u8, u64) set ON_STREAM_OPEN_FAILED
 
         fn set_on_transmit(u8, *const u8, size_t, *const IpAddr) set ON_TRANSMIT
 
         fn set_on_pollable_connection(u32) set ON_CONNECTION_POLLABLE
+
+        fn set_on_connection_stats(u8, *const u32, *const ConnectionStats, u32) set ON_CONNECTION_STATS
+
+        fn set_on_qlog(u32, *const u8, u32) set ON_QLOG
     }
 }
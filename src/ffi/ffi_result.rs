@@ -1,28 +1,61 @@
-use crate::proto_impl::FFIErrorKind;
+use crate::proto_impl::{
+    ErrorCategory,
+    FFIErrorKind,
+};
 
+use libc::size_t;
 use std::{
     any::Any,
-    cell::RefCell,
+    backtrace::Backtrace,
+    cell::{
+        Cell,
+        RefCell,
+    },
     ffi::CString,
     fmt,
     fmt::{
         Display,
         Formatter,
     },
+    panic,
     panic::{
         catch_unwind,
         UnwindSafe,
     },
+    ptr,
+    sync::Once,
 };
 
 thread_local!(
     static LAST_RESULT: RefCell<Option<LastResult>> = RefCell::new(None);
+    // The location and backtrace of the last panic on this thread, recorded by the panic hook.
+    static LAST_PANIC: RefCell<Option<(String, Option<String>)>> = const { RefCell::new(None) };
+    // Whether the `LAST_RESULT` was set, or deliberately kept, by the FFI call running on this thread.
+    static CONTEXT_SET: Cell<bool> = const { Cell::new(false) };
 );
 
+static PANIC_HOOK: Once = Once::new();
+
 /// The last `QuinnError`.
 #[derive(Debug)]
 pub struct LastResult {
     err: Option<FFIErrorKind>,
+    // The formatted error, referenced by `ErrorInfo::message`.
+    message: String,
+}
+
+/// Structured information about the last error, filled by `last_error_info`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct ErrorInfo {
+    /// The category of the error, `None` if no error occurred.
+    pub category: ErrorCategory,
+    /// The QUIC transport or application error code, the `ConnectErrorKind` for `ConnectFailed`, 0 when not applicable.
+    pub code: u64,
+    /// The utf8 encoded error message, valid until the next failing FFI call on the same thread.
+    pub message: *const u8,
+    /// The length of `message`.
+    pub message_len: size_t,
 }
 
 /// FFI safe result type.
@@ -60,6 +93,30 @@ impl FFIResult {
         FFIResult::new(FFIResultKind::ArgumentNull)
     }
 
+    pub fn would_block() -> Self {
+        FFIResult::new(FFIResultKind::WouldBlock)
+    }
+
+    pub fn panic() -> Self {
+        FFIResult::new(FFIResultKind::Panic)
+    }
+
+    pub fn handle_poisoned() -> Self {
+        FFIResult::new(FFIResultKind::HandlePoisoned)
+    }
+
+    pub fn invalid_argument() -> Self {
+        FFIResult::new(FFIResultKind::InvalidArgument)
+    }
+
+    pub fn connect_failed() -> Self {
+        FFIResult::new(FFIResultKind::ConnectFailed)
+    }
+
+    pub fn unknown_connection() -> Self {
+        FFIResult::new(FFIResultKind::UnknownConnection)
+    }
+
     pub fn is_err(&self) -> bool {
         self.kind != FFIResultKind::Ok
     }
@@ -67,11 +124,14 @@ impl FFIResult {
     /// Sets the `LAST_RESULT` context to the given `FFIErrorKind`.
     pub fn context(self, e: FFIErrorKind) -> Self {
         tracing::error!("with context {:?}", e);
-        LAST_RESULT.with(|last_result| {
-            let result = LastResult { err: Some(e) };
-            last_result.replace(Some(result));
-        });
+        set_last_result(e);
+
+        self
+    }
 
+    /// Returns the result without replacing the `LAST_RESULT`, for functions that report the last error itself.
+    pub fn keep_last_result(self) -> Self {
+        CONTEXT_SET.with(|context_set| context_set.set(true));
         self
     }
 
@@ -94,32 +154,95 @@ impl FFIResult {
         })
     }
 
-    /// Calls a function catching any panic and on panic sets the `LAST_RESULT`.
-    pub(super) fn catch(f: impl FnOnce() -> Self + UnwindSafe) -> Self {
+    /// Returns the category, code and message of the error in `LAST_RESULT`.
+    pub fn last_error_info() -> ErrorInfo {
         LAST_RESULT.with(|last_result| {
-            {
-                *last_result.borrow_mut() = None;
+            let last_result = last_result.borrow();
+
+            match last_result.as_ref() {
+                Some(LastResult {
+                    err: Some(error),
+                    message,
+                }) => ErrorInfo {
+                    category: error.category(),
+                    code: error.code(),
+                    message: message.as_ptr(),
+                    message_len: message.len(),
+                },
+                _ => ErrorInfo {
+                    category: ErrorCategory::None,
+                    code: 0,
+                    message: ptr::null(),
+                    message_len: 0,
+                },
             }
-            return match catch_unwind(f) {
-                Ok(result) => result,
-                Err(e) => {
-                    let extract_panic =
-                        || extract_panic(&e).map(|s| format!("internal panic with '{}'", s));
-
-                    // Set the last error to the panic message if it's not already set
-                    let mut ref_mut = last_result.borrow_mut();
-
-                    ref_mut.as_mut().map(|a| {
-                        *a = LastResult {
-                            err: Some(FFIErrorKind::io_error(&extract_panic().unwrap())),
-                        };
-                    });
-
-                    FFIResult::err()
-                }
-            };
         })
     }
+
+    /// Calls a function catching any panic and on panic sets the `LAST_RESULT`.
+    ///
+    /// The `LAST_RESULT` is kept until another call fails, so it can be retrieved by `last_error` after the failing call.
+    /// A failing call that did not set a context sets one describing its result kind, so the last error always belongs to it.
+    /// A panic results in `Panic`, the last error holds the panic message, location and, in debug builds, a backtrace.
+    pub(super) fn catch(f: impl FnOnce() -> Self + UnwindSafe) -> Self {
+        PANIC_HOOK.call_once(install_panic_hook);
+        CONTEXT_SET.with(|context_set| context_set.set(false));
+
+        match catch_unwind(f) {
+            Ok(result) => {
+                // Not logged like `context`, these are mostly expected results such as `WouldBlock`.
+                if result.is_err() && !CONTEXT_SET.with(Cell::get) {
+                    set_last_result(FFIErrorKind::FFIResultKind(result.kind.clone()));
+                }
+
+                result
+            }
+            Err(e) => {
+                let message = extract_panic(&e).unwrap_or_else(|| "Box<dyn Any>".to_owned());
+                let (location, backtrace) = LAST_PANIC
+                    .with(|last_panic| last_panic.borrow_mut().take())
+                    .unwrap_or_else(|| ("<unknown>".to_owned(), None));
+
+                FFIResult::panic().context(FFIErrorKind::Panic {
+                    message,
+                    location,
+                    backtrace,
+                })
+            }
+        }
+    }
+}
+
+/// Replaces the `LAST_RESULT` of this thread with the error.
+fn set_last_result(e: FFIErrorKind) {
+    CONTEXT_SET.with(|context_set| context_set.set(true));
+    LAST_RESULT.with(|last_result| {
+        let result = LastResult {
+            message: e.to_string(),
+            err: Some(e),
+        };
+        last_result.replace(Some(result));
+    });
+}
+
+/// Chains a panic hook recording the location and, in debug builds, the backtrace of a panic for `FFIResult::catch`.
+fn install_panic_hook() {
+    let previous_hook = panic::take_hook();
+
+    panic::set_hook(Box::new(move |info| {
+        let location = info
+            .location()
+            .map(|location| location.to_string())
+            .unwrap_or_else(|| "<unknown>".to_owned());
+        let backtrace = cfg!(debug_assertions).then(|| Backtrace::force_capture().to_string());
+
+        // The hook might run while the thread is destroyed, after which the thread local is not accessible.
+        let _ = LAST_PANIC.try_with(|last_panic| {
+            *last_panic.borrow_mut() = Some((location, backtrace));
+        });
+
+        previous_hook(info);
+    }));
 }
 
 fn extract_panic(err: &Box<dyn Any + Send + 'static>) -> Option<String> {
@@ -142,6 +265,15 @@ impl Display for FFIResult {
                 write!(f, "There is no data in the buffer to be read.")?
             }
             FFIResultKind::ArgumentNull => write!(f, "An argument was null.")?,
+            FFIResultKind::WouldBlock => write!(f, "The operation would block, try again later.")?,
+            FFIResultKind::Panic => write!(f, "The library panicked, see the last error.")?,
+            FFIResultKind::HandlePoisoned => write!(
+                f,
+                "The handle is unusable after a panic while it was in use."
+            )?,
+            FFIResultKind::InvalidArgument => write!(f, "An argument was invalid.")?,
+            FFIResultKind::ConnectFailed => write!(f, "The connection could not be created.")?,
+            FFIResultKind::UnknownConnection => write!(f, "The connection is unknown.")?,
         }
         Ok(())
     }
@@ -158,8 +290,19 @@ impl<T> From<Result<T, FFIErrorKind>> for FFIResult {
                     FFIResultKind::BufferToSmall => FFIResult::buffer_too_small(),
                     FFIResultKind::BufferBlocked => FFIResult::buffer_blocked(),
                     FFIResultKind::ArgumentNull => FFIResult::argument_null(),
+                    FFIResultKind::WouldBlock => FFIResult::would_block(),
+                    FFIResultKind::Panic => FFIResult::panic(),
+                    FFIResultKind::HandlePoisoned => {
+                        FFIResult::handle_poisoned().context(FFIErrorKind::error(
+                            ErrorCategory::Handle,
+                            "The handle is poisoned by a panic in an earlier call",
+                        ))
+                    }
+                    FFIResultKind::InvalidArgument => FFIResult::invalid_argument(),
+                    FFIResultKind::ConnectFailed => FFIResult::connect_failed(),
+                    FFIResultKind::UnknownConnection => FFIResult::unknown_connection(),
                 },
-                e => FFIResult::err().context(e),
+                e => FFIResult::new(e.result_kind()).context(e),
             },
         }
     }
@@ -185,6 +328,18 @@ pub enum FFIResultKind {
     BufferBlocked,
     /// A argument to the FFI function was not initialized.
     ArgumentNull,
+    /// The operation can not complete now, retry when notified.
+    WouldBlock,
+    /// The library panicked, the last error holds the panic message and location.
+    Panic,
+    /// The handle can not be used anymore because an earlier call panicked while using it.
+    HandlePoisoned,
+    /// A argument to the FFI function was invalid, e.g. not utf8 or out of range.
+    InvalidArgument,
+    /// The connection could not be created, the code of the last error holds the `ConnectErrorKind`.
+    ConnectFailed,
+    /// The connection is not known to the endpoint.
+    UnknownConnection,
 }
 
 /// Error with code and reason.
@@ -27,6 +27,14 @@ where
     pub unsafe fn as_ref(&self) -> &T {
         &*self.0
     }
+
+    // The pointer must be nonnull, the length is correct, and will remain valid
+    pub unsafe fn as_slice(&self, len: usize) -> &[T]
+    where
+        T: Sized,
+    {
+        slice::from_raw_parts(self.0, len)
+    }
 }
 
 impl<'a> Ref<'a, u8> {
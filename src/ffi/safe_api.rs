@@ -5,9 +5,11 @@
 //! It is more safe then the unsafe api however it introduces some extra logic to keep this safe which could come at a little performance cost.
 
 use crate::proto_impl::{
+    ClientConfigImpl,
     ConnectionImpl,
     EndpointImpl,
     FFIErrorKind,
+    ServerConfigImpl,
 };
 
 use crate::ffi::{
@@ -15,28 +17,52 @@ use crate::ffi::{
     HandleMut,
 };
 
-use std::sync::{
-    Arc,
-    Mutex,
+use std::{
+    cell::Cell,
+    sync::{
+        Arc,
+        Mutex,
+    },
 };
 
+thread_local!(
+    // The FFI function invoked on this thread that is not traced yet.
+    static INVOKED_FUNCTION: Cell<Option<&'static str>> = const { Cell::new(None) };
+);
+
+/// Records the FFI function invoked on this thread, it is traced by [`trace_invoke`].
+pub fn set_invoked_function(name: &'static str) {
+    INVOKED_FUNCTION.with(|function| function.set(Some(name)));
+}
+
+/// Traces the FFI function invoked on this thread, once per invocation.
+///
+/// Handles call this on their first access after entering their span,
+/// so the trace is attributed to the endpoint or connection the function operates on.
+pub fn trace_invoke() {
+    if let Some(name) = INVOKED_FUNCTION.with(Cell::take) {
+        tracing::trace!("FFI invoke: {:?}", name);
+    }
+}
+
 // Mutex required for unwind safeness due to possible interior mutability.
-pub type RustlsClientConfigHandle<'a> = FFIHandleMut<'a, Mutex<quinn_proto::ClientConfig>>;
+pub type RustlsClientConfigHandle<'a> = FFIHandleMut<'a, Mutex<ClientConfigImpl>>;
 // Mutex required for unwind safeness due to possible interior mutability.
-pub type RustlsServerConfigHandle<'a> = FFIHandleMut<'a, Mutex<quinn_proto::ServerConfig>>;
+pub type RustlsServerConfigHandle<'a> = FFIHandleMut<'a, Mutex<ServerConfigImpl>>;
 // Mutex require d for unwind safeness due to possible interior mutability.
 pub type EndpointHandle<'a> = FFIHandleMut<'a, Arc<Mutex<EndpointImpl>>>;
 // Mutex required for unwind safeness due to possible interior mutability.
 pub type ConnectionHandle<'a> = FFIHandleMut<'a, Arc<Mutex<ConnectionImpl>>>;
 
 impl<'a> HandleMut for RustlsClientConfigHandle<'a> {
-    type Inner = quinn_proto::ClientConfig;
+    type Inner = ClientConfigImpl;
 
     fn ref_access(
         &self,
         cb: &mut dyn FnMut(&Self::Inner) -> Result<(), FFIErrorKind>,
     ) -> Result<(), FFIErrorKind> {
-        let lock = &self.lock().unwrap();
+        let lock = &self.lock()?;
+        trace_invoke();
         cb(lock)
     }
 
@@ -44,8 +70,8 @@ impl<'a> HandleMut for RustlsClientConfigHandle<'a> {
         &mut self,
         cb: &mut dyn FnMut(&mut Self::Inner) -> Result<(), FFIErrorKind>,
     ) -> Result<(), FFIErrorKind> {
-        let mut lock = self.lock().unwrap();
-
+        let mut lock = self.lock()?;
+        trace_invoke();
         cb(&mut lock)
     }
 
@@ -55,13 +81,14 @@ impl<'a> HandleMut for RustlsClientConfigHandle<'a> {
 }
 
 impl<'a> HandleMut for RustlsServerConfigHandle<'a> {
-    type Inner = quinn_proto::ServerConfig;
+    type Inner = ServerConfigImpl;
 
     fn ref_access(
         &self,
         cb: &mut dyn FnMut(&Self::Inner) -> Result<(), FFIErrorKind>,
     ) -> Result<(), FFIErrorKind> {
-        let lock = self.lock().unwrap();
+        let lock = self.lock()?;
+        trace_invoke();
         cb(&lock)
     }
 
@@ -69,7 +96,8 @@ impl<'a> HandleMut for RustlsServerConfigHandle<'a> {
         &mut self,
         cb: &mut dyn FnMut(&mut Self::Inner) -> Result<(), FFIErrorKind>,
     ) -> Result<(), FFIErrorKind> {
-        let mut lock = self.lock().unwrap();
+        let mut lock = self.lock()?;
+        trace_invoke();
         cb(&mut lock)
     }
 
@@ -85,7 +113,9 @@ impl<'a> HandleMut for EndpointHandle<'a> {
         &self,
         cb: &mut dyn FnMut(&Self::Inner) -> Result<(), FFIErrorKind>,
     ) -> Result<(), FFIErrorKind> {
-        let lock = self.lock().unwrap();
+        let lock = self.lock()?;
+        let _entered = lock.span().clone().entered();
+        trace_invoke();
         cb(&lock)
     }
 
@@ -94,7 +124,9 @@ impl<'a> HandleMut for EndpointHandle<'a> {
         cb: &mut dyn FnMut(&mut Self::Inner) -> Result<(), FFIErrorKind>,
     ) -> Result<(), FFIErrorKind> {
         //println!(" ++ endpoint lock");
-        let mut lock = self.lock().unwrap();
+        let mut lock = self.lock()?;
+        let _entered = lock.span().clone().entered();
+        trace_invoke();
         let a = cb(&mut lock);
         //println!(" ++ end endpoint lock");
         a
@@ -112,7 +144,9 @@ impl<'a> HandleMut for ConnectionHandle<'a> {
         &self,
         cb: &mut dyn FnMut(&Self::Inner) -> Result<(), FFIErrorKind>,
     ) -> Result<(), FFIErrorKind> {
-        let lock = self.lock().unwrap();
+        let lock = self.lock()?;
+        let _entered = lock.span().clone().entered();
+        trace_invoke();
         cb(&lock)
     }
 
@@ -121,7 +155,9 @@ impl<'a> HandleMut for ConnectionHandle<'a> {
         cb: &mut dyn FnMut(&mut Self::Inner) -> Result<(), FFIErrorKind>,
     ) -> Result<(), FFIErrorKind> {
         //println!("\t++ connection lock");
-        let mut lock = self.lock().unwrap();
+        let mut lock = self.lock()?;
+        let _entered = lock.span().clone().entered();
+        trace_invoke();
         let a = cb(&mut lock);
         //println!("\t-- end connection lock");
         a
@@ -150,7 +186,8 @@ macro_rules! ffi {
             #[allow(unsafe_code, unused_attributes)]
             #[no_mangle]
             pub unsafe extern "cdecl" fn $name( $($arg_ident : $arg_ty),* ) -> FFIResult {
-                tracing::trace!("FFI invoke: {:?}", stringify!($name));
+                // Traced on the first handle access, inside the span of the handle, or else once the function returns.
+                $crate::ffi::set_invoked_function(stringify!($name));
 
                 #[allow(unused_mut)]
                 fn call( $(mut $arg_ident: $arg_ty),* ) -> FFIResult {
@@ -163,7 +200,9 @@ macro_rules! ffi {
                     $body
                 }
 
-                FFIResult::catch(move || call( $($arg_ident),* ))
+                let result = FFIResult::catch(move || call( $($arg_ident),* ));
+                $crate::ffi::trace_invoke();
+                result
             }
         )*
     };
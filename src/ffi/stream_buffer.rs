@@ -0,0 +1,53 @@
+use crate::ffi::IsNull;
+use bytes::Bytes;
+use libc::size_t;
+use std::{
+    ptr,
+    slice,
+};
+
+/// A byte buffer passed across the FFI boundary as pointer and length.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct StreamBuffer {
+    pub data: *mut u8,
+    pub len: size_t,
+}
+
+// The buffer is semantically `&[u8]` or, when leased, `Box<[u8]>`.
+unsafe impl Send for StreamBuffer {}
+unsafe impl Sync for StreamBuffer {}
+
+impl StreamBuffer {
+    /// Allocates a zeroed buffer that is leased to the caller until it is returned with [`StreamBuffer::into_bytes`].
+    pub fn lease(len: usize) -> Self {
+        let buffer = Box::into_raw(vec![0; len].into_boxed_slice());
+
+        StreamBuffer {
+            data: buffer as *mut u8,
+            len,
+        }
+    }
+
+    /// Returns the bytes of the buffer.
+    ///
+    /// The pointer must be nonnull, the length is correct, and will remain valid.
+    pub unsafe fn as_bytes(&self) -> &[u8] {
+        slice::from_raw_parts(self.data, self.len)
+    }
+
+    /// Takes back ownership of a leased buffer without copying it.
+    ///
+    /// The buffer must be returned by [`StreamBuffer::lease`] with its length unchanged and must not be used afterwards.
+    pub unsafe fn into_bytes(self) -> Bytes {
+        Bytes::from(Box::from_raw(ptr::slice_from_raw_parts_mut(
+            self.data, self.len,
+        )))
+    }
+}
+
+impl IsNull for StreamBuffer {
+    fn is_null(&self) -> bool {
+        self.data.is_null()
+    }
+}
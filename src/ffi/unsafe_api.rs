@@ -3,9 +3,11 @@
 use crate::{
     ffi::HandleMut,
     proto_impl::{
+        ClientConfigImpl,
         ConnectionImpl,
         EndpointImpl,
         FFIErrorKind,
+        ServerConfigImpl,
     },
 };
 
@@ -19,22 +21,22 @@ use std::sync::{
 };
 
 // Mutex required for unwind safeness due to possible interior mutability.
-pub type RustlsClientConfigHandle<'a> = FFIHandleMut<'a, Mutex<quinn_proto::ClientConfig>>;
+pub type RustlsClientConfigHandle<'a> = FFIHandleMut<'a, Mutex<ClientConfigImpl>>;
 // Mutex required for unwind safeness due to possible interior mutability.
-pub type RustlsServerConfigHandle<'a> = FFIHandleMut<'a, Mutex<quinn_proto::ServerConfig>>;
+pub type RustlsServerConfigHandle<'a> = FFIHandleMut<'a, Mutex<ServerConfigImpl>>;
 // Mutex required for unwind safeness due to possible interior mutability.
 pub type EndpointHandle<'a> = FFIHandleMut<'a, Arc<Mutex<EndpointImpl>>>;
 // Mutex required for unwind safeness due to possible interior mutability.
 pub type ConnectionHandle<'a> = FFIHandleMut<'a, Arc<Mutex<ConnectionImpl>>>;
 
 impl<'a> HandleMut for RustlsClientConfigHandle<'a> {
-    type Inner = quinn_proto::ClientConfig;
+    type Inner = ClientConfigImpl;
 
     fn ref_access(
         &self,
         cb: &mut dyn FnMut(&Self::Inner) -> Result<(), FFIErrorKind>,
     ) -> Result<(), FFIErrorKind> {
-        let lock = &self.lock().unwrap();
+        let lock = &self.lock()?;
         cb(lock)
     }
 
@@ -42,7 +44,7 @@ impl<'a> HandleMut for RustlsClientConfigHandle<'a> {
         &mut self,
         cb: &mut dyn FnMut(&mut Self::Inner) -> Result<(), FFIErrorKind>,
     ) -> Result<(), FFIErrorKind> {
-        let mut lock = self.lock().unwrap();
+        let mut lock = self.lock()?;
 
         cb(&mut lock)
     }
@@ -59,7 +61,8 @@ impl<'a> HandleMut for EndpointHandle<'a> {
         &self,
         cb: &mut dyn FnMut(&Self::Inner) -> Result<(), FFIErrorKind>,
     ) -> Result<(), FFIErrorKind> {
-        let mut lock = self.lock().unwrap();
+        let mut lock = self.lock()?;
+        let _entered = lock.span().clone().entered();
 
         cb(&mut lock)
     }
@@ -68,7 +71,8 @@ impl<'a> HandleMut for EndpointHandle<'a> {
         &mut self,
         cb: &mut dyn FnMut(&mut Self::Inner) -> Result<(), FFIErrorKind>,
     ) -> Result<(), FFIErrorKind> {
-        let mut lock = self.lock().unwrap();
+        let mut lock = self.lock()?;
+        let _entered = lock.span().clone().entered();
 
         cb(&mut lock)
     }
@@ -79,13 +83,13 @@ impl<'a> HandleMut for EndpointHandle<'a> {
 }
 
 impl<'a> HandleMut for RustlsServerConfigHandle<'a> {
-    type Inner = quinn_proto::ServerConfig;
+    type Inner = ServerConfigImpl;
 
     fn ref_access(
         &self,
         cb: &mut dyn FnMut(&Self::Inner) -> Result<(), FFIErrorKind>,
     ) -> Result<(), FFIErrorKind> {
-        let lock = &self.lock().unwrap();
+        let lock = &self.lock()?;
 
         cb(lock)
     }
@@ -94,7 +98,7 @@ impl<'a> HandleMut for RustlsServerConfigHandle<'a> {
         &mut self,
         cb: &mut dyn FnMut(&mut Self::Inner) -> Result<(), FFIErrorKind>,
     ) -> Result<(), FFIErrorKind> {
-        let mut lock = self.lock().unwrap();
+        let mut lock = self.lock()?;
 
         cb(&mut lock)
     }
@@ -111,7 +115,8 @@ impl<'a> HandleMut for ConnectionHandle<'a> {
         &self,
         cb: &mut dyn FnMut(&Self::Inner) -> Result<(), FFIErrorKind>,
     ) -> Result<(), FFIErrorKind> {
-        let lock = &self.lock().unwrap();
+        let lock = &self.lock()?;
+        let _entered = lock.span().clone().entered();
 
         cb(lock)
     }
@@ -120,7 +125,8 @@ impl<'a> HandleMut for ConnectionHandle<'a> {
         &mut self,
         cb: &mut dyn FnMut(&mut Self::Inner) -> Result<(), FFIErrorKind>,
     ) -> Result<(), FFIErrorKind> {
-        let mut lock = self.lock().unwrap();
+        let mut lock = self.lock()?;
+        let _entered = lock.span().clone().entered();
 
         let a = cb(&mut lock);
         drop(lock);
@@ -1,18 +1,80 @@
 //! Quinn-proto implementation, similar to `quinn` but without the async runtime and some differences to make it fit with FFi applications.
 
 pub use addr::IpAddr;
+pub use cert_resolver::{
+    decode_certificate_chain,
+    SniCertResolver,
+};
+pub use config::{
+    decode_alpn_protocols,
+    ClientConfigImpl,
+    ServerConfigImpl,
+};
 pub use connection::{
     ConnectionEvent,
     ConnectionImpl,
+    ConnectionSide,
+    StreamDescriptor,
+    StreamReadStatus,
+    ZeroRttState,
 };
 pub use endpoint::{
     EndpointEvent,
     EndpointImpl,
     EndpointPoller,
 };
-pub use result::FFIErrorKind;
+#[cfg(feature = "key-log")]
+pub use key_log::{
+    CallbackKeyLog,
+    FileKeyLog,
+};
+#[cfg(feature = "debug")]
+pub use log::{
+    enable_log,
+    set_log_filter,
+    CallbackLayer,
+    LogLevel,
+};
+pub use metrics::{
+    register_endpoint,
+    render_prometheus,
+    store_pending_metrics,
+    take_pending_or_render_prometheus,
+};
+pub use qlog::{
+    QlogSink,
+    QlogWriter,
+};
+pub use result::{
+    ConnectErrorKind,
+    ErrorCategory,
+    FFIErrorKind,
+};
+pub use self_signed::SelfSignedCertificate;
+pub use session_cache::SessionCache;
+pub use stats::{
+    ConnectionStats,
+    EndpointStats,
+    FrameStats,
+    HandshakeFailures,
+    PathStats,
+    UdpStats,
+};
+pub use stream_stats::StreamStats;
 
 mod addr;
+mod cert_resolver;
+mod config;
 mod connection;
 mod endpoint;
+#[cfg(feature = "key-log")]
+mod key_log;
+#[cfg(feature = "debug")]
+mod log;
+mod metrics;
+mod qlog;
 mod result;
+mod self_signed;
+mod session_cache;
+mod stats;
+mod stream_stats;
@@ -0,0 +1,182 @@
+use crate::proto_impl::{
+    ErrorCategory,
+    FFIErrorKind,
+};
+use rustls::{
+    server::{
+        ClientHello,
+        ResolvesServerCert,
+    },
+    sign::{
+        self,
+        CertifiedKey,
+    },
+    Certificate,
+    PrivateKey,
+};
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc,
+        RwLock,
+    },
+};
+
+/// Selects the server certificate by the SNI server name the client sent.
+///
+/// Certificates can be added, replaced and removed while endpoints are using the resolver.
+/// Changes only affect new handshakes, established connections keep their certificate.
+#[derive(Default)]
+pub struct SniCertResolver {
+    certificates: RwLock<HashMap<String, Arc<CertifiedKey>>>,
+}
+
+impl SniCertResolver {
+    /// Adds or replaces the certificate for the given server name.
+    ///
+    /// An empty server name sets the default certificate that is used when the client sends no or an unknown server name.
+    pub fn set_certificate(
+        &self,
+        server_name: &str,
+        cert_chain: Vec<Certificate>,
+        key: &PrivateKey,
+    ) -> Result<(), FFIErrorKind> {
+        let key = sign::any_supported_type(key)
+            .map_err(|_| FFIErrorKind::error(ErrorCategory::Tls, "Unsupported private key type"))?;
+
+        self.certificates.write().unwrap().insert(
+            server_name.to_ascii_lowercase(),
+            Arc::new(CertifiedKey::new(cert_chain, key)),
+        );
+
+        Ok(())
+    }
+
+    /// Removes the certificate for the given server name, returns whether a certificate was removed.
+    pub fn remove_certificate(&self, server_name: &str) -> bool {
+        self.certificates
+            .write()
+            .unwrap()
+            .remove(&server_name.to_ascii_lowercase())
+            .is_some()
+    }
+}
+
+impl ResolvesServerCert for SniCertResolver {
+    fn resolve(&self, client_hello: ClientHello) -> Option<Arc<CertifiedKey>> {
+        let certificates = self.certificates.read().unwrap();
+
+        client_hello
+            .server_name()
+            .and_then(|name| certificates.get(&name.to_ascii_lowercase()))
+            .or_else(|| certificates.get(""))
+            .cloned()
+    }
+}
+
+/// Splits concatenated DER-encoded X.509 certificates into a certificate chain.
+pub fn decode_certificate_chain(mut bytes: &[u8]) -> Result<Vec<Certificate>, FFIErrorKind> {
+    let mut chain = Vec::new();
+
+    while !bytes.is_empty() {
+        let len = der_element_len(bytes).ok_or_else(|| {
+            FFIErrorKind::error(ErrorCategory::Tls, "Malformed DER certificate chain")
+        })?;
+
+        chain.push(Certificate(bytes[..len].to_vec()));
+        bytes = &bytes[len..];
+    }
+
+    if chain.is_empty() {
+        return Err(FFIErrorKind::error(
+            ErrorCategory::Tls,
+            "Certificate chain is empty",
+        ));
+    }
+
+    Ok(chain)
+}
+
+/// Returns the total length, header included, of the DER element at the start of `bytes`.
+fn der_element_len(bytes: &[u8]) -> Option<usize> {
+    let first_len_byte = *bytes.get(1)? as usize;
+
+    let (header_len, content_len) = if first_len_byte < 0x80 {
+        (2, first_len_byte)
+    } else {
+        let len_bytes = first_len_byte & 0x7f;
+        if len_bytes == 0 || len_bytes > 4 {
+            return None;
+        }
+
+        let content_len = bytes
+            .get(2..2 + len_bytes)?
+            .iter()
+            .fold(0, |len, byte| (len << 8) | *byte as usize);
+
+        (2 + len_bytes, content_len)
+    };
+
+    let len = header_len + content_len;
+    if len > bytes.len() {
+        return None;
+    }
+
+    Some(len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::decode_certificate_chain;
+
+    fn der_sequence(content: &[u8]) -> Vec<u8> {
+        let mut element = vec![0x30];
+        if content.len() < 0x80 {
+            element.push(content.len() as u8);
+        } else {
+            element.push(0x82);
+            element.extend_from_slice(&(content.len() as u16).to_be_bytes());
+        }
+        element.extend_from_slice(content);
+        element
+    }
+
+    #[test]
+    fn splits_concatenated_certificates() {
+        let leaf = der_sequence(&[1, 2, 3]);
+        let intermediate = der_sequence(&[7; 300]);
+        let bytes = [leaf.clone(), intermediate.clone()].concat();
+
+        let chain = decode_certificate_chain(&bytes).unwrap();
+
+        assert_eq!(chain.len(), 2);
+        assert_eq!(chain[0].0, leaf);
+        assert_eq!(chain[1].0, intermediate);
+    }
+
+    #[test]
+    fn accepts_zero_length_element() {
+        let chain = decode_certificate_chain(&der_sequence(&[])).unwrap();
+        assert_eq!(chain[0].0, vec![0x30, 0x00]);
+    }
+
+    #[test]
+    fn rejects_empty_chain() {
+        assert!(decode_certificate_chain(&[]).is_err());
+    }
+
+    #[test]
+    fn rejects_truncated_certificate() {
+        let bytes = der_sequence(&[7; 300]);
+
+        assert!(decode_certificate_chain(&bytes[..bytes.len() - 1]).is_err());
+        assert!(decode_certificate_chain(&bytes[..3]).is_err());
+        assert!(decode_certificate_chain(&bytes[..1]).is_err());
+    }
+
+    #[test]
+    fn rejects_unsupported_length_encoding() {
+        assert!(decode_certificate_chain(&[0x30, 0x80]).is_err());
+        assert!(decode_certificate_chain(&[0x30, 0x85, 0, 0, 0, 0, 1]).is_err());
+    }
+}
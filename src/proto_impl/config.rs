@@ -0,0 +1,162 @@
+use crate::{
+    proto,
+    proto_impl::{
+        ErrorCategory,
+        FFIErrorKind,
+        SessionCache,
+        SniCertResolver,
+    },
+};
+use std::sync::Arc;
+
+/// Server configuration that keeps the rustls crypto configuration next to the quinn configuration.
+///
+/// Quinn stores the crypto configuration as a trait object.
+/// Keeping the rustls configuration around allows TLS options to be changed after the configuration is created.
+pub struct ServerConfigImpl {
+    crypto: rustls::ServerConfig,
+    inner: proto::ServerConfig,
+    cert_resolver: Option<Arc<SniCertResolver>>,
+}
+
+impl ServerConfigImpl {
+    /// Creates a new `ServerConfigImpl` from a rustls server configuration.
+    pub fn new(crypto: rustls::ServerConfig) -> Self {
+        let inner = proto::ServerConfig::with_crypto(Arc::new(crypto.clone()));
+        ServerConfigImpl {
+            crypto,
+            inner,
+            cert_resolver: None,
+        }
+    }
+
+    /// Creates a new `ServerConfigImpl` that selects certificates with the given SNI resolver.
+    ///
+    /// The rustls configuration must use `cert_resolver` as its certificate resolver.
+    pub fn with_cert_resolver(
+        crypto: rustls::ServerConfig,
+        cert_resolver: Arc<SniCertResolver>,
+    ) -> Self {
+        let mut config = ServerConfigImpl::new(crypto);
+        config.cert_resolver = Some(cert_resolver);
+        config
+    }
+
+    /// Returns the SNI certificate resolver if this configuration was created with one.
+    pub fn cert_resolver(&self) -> Option<&SniCertResolver> {
+        self.cert_resolver.as_deref()
+    }
+
+    /// Modifies the rustls configuration and updates the quinn configuration with the result.
+    pub fn update_crypto(&mut self, f: impl FnOnce(&mut rustls::ServerConfig)) {
+        f(&mut self.crypto);
+        self.inner.crypto = Arc::new(self.crypto.clone());
+    }
+
+    /// Returns the rustls server configuration.
+    pub fn crypto(&self) -> &rustls::ServerConfig {
+        &self.crypto
+    }
+
+    /// Returns the quinn server configuration used to create endpoints.
+    pub fn proto_config(&self) -> proto::ServerConfig {
+        self.inner.clone()
+    }
+}
+
+/// Client configuration that keeps the rustls crypto configuration next to the quinn configuration.
+///
+/// See [`ServerConfigImpl`] for why the rustls configuration is kept.
+pub struct ClientConfigImpl {
+    crypto: rustls::ClientConfig,
+    inner: proto::ClientConfig,
+    session_cache: Arc<SessionCache>,
+}
+
+impl ClientConfigImpl {
+    /// Creates a new `ClientConfigImpl` from a rustls client configuration.
+    ///
+    /// The session storage of `crypto` is replaced by a [`SessionCache`] that can be serialized.
+    pub fn new(mut crypto: rustls::ClientConfig) -> Self {
+        let session_cache = Arc::new(SessionCache::default());
+        crypto.session_storage = session_cache.clone();
+
+        let inner = proto::ClientConfig::new(Arc::new(crypto.clone()));
+        ClientConfigImpl {
+            crypto,
+            inner,
+            session_cache,
+        }
+    }
+
+    /// Returns the session cache storing the TLS resumption tickets.
+    pub fn session_cache(&self) -> &SessionCache {
+        &self.session_cache
+    }
+
+    /// Modifies the rustls configuration and updates the quinn configuration with the result.
+    pub fn update_crypto(&mut self, f: impl FnOnce(&mut rustls::ClientConfig)) {
+        f(&mut self.crypto);
+        self.inner.crypto = Arc::new(self.crypto.clone());
+    }
+
+    /// Returns the rustls client configuration.
+    pub fn crypto(&self) -> &rustls::ClientConfig {
+        &self.crypto
+    }
+
+    /// Returns the quinn client configuration used to connect.
+    pub fn proto_config(&self) -> proto::ClientConfig {
+        self.inner.clone()
+    }
+}
+
+/// Decodes a list of ALPN protocols.
+///
+/// The list uses the TLS wire format: each protocol is prefixed with its length as a single byte.
+pub fn decode_alpn_protocols(mut bytes: &[u8]) -> Result<Vec<Vec<u8>>, FFIErrorKind> {
+    let mut protocols = Vec::new();
+
+    while let Some((&len, rest)) = bytes.split_first() {
+        let len = len as usize;
+
+        if len == 0 || rest.len() < len {
+            return Err(FFIErrorKind::error(
+                ErrorCategory::Config,
+                "Malformed ALPN protocol list",
+            ));
+        }
+
+        protocols.push(rest[..len].to_vec());
+        bytes = &rest[len..];
+    }
+
+    Ok(protocols)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::decode_alpn_protocols;
+
+    #[test]
+    fn decodes_protocols() {
+        let protocols = decode_alpn_protocols(b"\x02h3\x08http/1.1").unwrap();
+        assert_eq!(protocols, vec![b"h3".to_vec(), b"http/1.1".to_vec()]);
+    }
+
+    #[test]
+    fn decodes_empty_list() {
+        assert!(decode_alpn_protocols(&[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn rejects_zero_length_protocol() {
+        assert!(decode_alpn_protocols(b"\x02h3\x00").is_err());
+    }
+
+    #[test]
+    fn rejects_truncated_protocol() {
+        assert!(decode_alpn_protocols(b"\x02h3\x08http").is_err());
+        assert!(decode_alpn_protocols(b"\x05").is_err());
+    }
+}
@@ -1,21 +1,55 @@
 use crate::{
-    ffi::callbacks,
+    ffi::{
+        callbacks,
+        FFIResultKind,
+    },
     proto,
     proto::VarInt,
     proto_impl::{
         endpoint::EndpointEvent,
-        result::FFIErrorKind,
+        qlog::{
+            QlogSink,
+            QlogWriter,
+        },
+        result::{
+            ErrorCategory,
+            FFIErrorKind,
+        },
+        ConnectionStats,
+        StreamStats,
     },
 };
 use bytes::Bytes;
-use quinn_proto::StreamEvent;
+use quinn_proto::{
+    crypto::rustls::HandshakeData,
+    Dir,
+    ReadError,
+    Side,
+    StreamEvent,
+    StreamId,
+    WriteError,
+};
 use std::{
+    collections::{
+        HashMap,
+        HashSet,
+        VecDeque,
+    },
+    mem,
+    ptr,
     sync::{
         mpsc,
         mpsc::Sender,
     },
     time::Instant,
 };
+use tracing::Span;
+
+/// Default maximum number of bytes a connection buffers for `write_all`.
+const DEFAULT_WRITE_BUFFER_LIMIT: usize = 1024 * 1024;
+
+/// Number of closed streams whose final statistics are kept, the oldest are dropped when exceeded.
+const MAX_CLOSED_STREAM_STATS: usize = 64;
 
 /// Events for the connection.
 #[derive(Debug)]
@@ -28,6 +62,69 @@ pub enum ConnectionEvent {
     Ping,
 }
 
+/// The state of a 0-RTT attempt.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ZeroRttState {
+    /// The connection did not attempt 0-RTT.
+    NotAttempted,
+    /// 0-RTT data can be sent, the handshake is not yet completed.
+    Pending,
+    /// The server accepted the 0-RTT data.
+    Accepted,
+    /// The server rejected the 0-RTT data.
+    ///
+    /// Quinn discards the streams opened during 0-RTT, the client application has to open them again and resend their data.
+    Rejected,
+}
+
+/// Whether more data can be read from a stream after a read.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamReadStatus {
+    /// No more data is currently available, wait for the stream to become readable.
+    Blocked,
+    /// The buffer was filled, more data might be available.
+    Readable,
+    /// The peer finished the stream, all data has been read.
+    Finished,
+    /// The peer reset the stream, no more data can be read.
+    Reset,
+}
+
+/// The side of a connection, or the side that initiated a stream.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionSide {
+    /// The side that connected, as a stream initiator the stream was opened by the client.
+    Client,
+    /// The side that accepted the connection, as a stream initiator the stream was opened by the server.
+    Server,
+}
+
+impl From<Side> for ConnectionSide {
+    fn from(side: Side) -> Self {
+        match side {
+            Side::Client => ConnectionSide::Client,
+            Side::Server => ConnectionSide::Server,
+        }
+    }
+}
+
+/// Describes a stream passed to the stream callbacks.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct StreamDescriptor {
+    /// The id of the stream.
+    pub id: u64,
+    /// The direction of the stream, 0 for bidirectional and 1 for unidirectional.
+    pub dir: u8,
+    /// The side that opened the stream.
+    pub initiator: ConnectionSide,
+    /// Whether the stream was opened by this side of the connection.
+    pub locally_opened: bool,
+}
+
 /// A QUIC connection using quinn-proto.
 pub struct ConnectionImpl {
     pub(crate) inner: proto::Connection,
@@ -37,6 +134,22 @@ pub struct ConnectionImpl {
     timer_deadline: Option<Instant>,
     last_poll: Instant,
     endpoint_poll_notifier: Option<Sender<i8>>,
+    zero_rtt_attempted: bool,
+    handshake_completed: bool,
+    default_stream_priorities: [i32; 2],
+    push_stream_data: bool,
+    readable_streams: Vec<StreamId>,
+    write_queues: HashMap<StreamId, VecDeque<Bytes>>,
+    queued_bytes: usize,
+    write_buffer_limit: usize,
+    deferred_finishes: HashSet<StreamId>,
+    unacked_finishes: HashSet<StreamId>,
+    graceful_close: Option<(VarInt, Bytes)>,
+    stream_stats: HashMap<StreamId, StreamStats>,
+    closed_stream_stats: VecDeque<(StreamId, StreamStats)>,
+    deferred_opens: [VecDeque<u64>; 2],
+    qlog: Option<QlogWriter>,
+    span: Span,
 }
 
 impl ConnectionImpl {
@@ -46,7 +159,11 @@ impl ConnectionImpl {
         recv: mpsc::Receiver<ConnectionEvent>,
         endpoint_events_tx: Sender<(proto::ConnectionHandle, EndpointEvent)>,
         endpoint_poll_notifier: Option<Sender<i8>>,
+        span: Span,
     ) -> ConnectionImpl {
+        // Clients decide on 0-RTT when the connection is created, based on the available session ticket.
+        let zero_rtt_attempted = inner.side().is_client() && inner.has_0rtt();
+
         ConnectionImpl {
             inner,
             connection_events: recv,
@@ -55,6 +172,22 @@ impl ConnectionImpl {
             timer_deadline: None,
             last_poll: Instant::now(),
             endpoint_poll_notifier,
+            zero_rtt_attempted,
+            handshake_completed: false,
+            default_stream_priorities: [0; 2],
+            push_stream_data: false,
+            readable_streams: Vec::new(),
+            write_queues: HashMap::new(),
+            queued_bytes: 0,
+            write_buffer_limit: DEFAULT_WRITE_BUFFER_LIMIT,
+            deferred_finishes: HashSet::new(),
+            unacked_finishes: HashSet::new(),
+            graceful_close: None,
+            stream_stats: HashMap::new(),
+            closed_stream_stats: VecDeque::new(),
+            deferred_opens: [VecDeque::new(), VecDeque::new()],
+            qlog: None,
+            span,
         }
     }
 }
@@ -67,19 +200,53 @@ impl ConnectionImpl {
     /// 3. Handles timeout
     /// 4. Handles endpoint events.
     /// 5. Handles app events.
+    /// 6. Opens streams requested with `open_stream_deferred`.
+    /// 7. Pushes stream data when push mode is enabled.
     ///
     /// Polling the connection might result in callbacks to the client application.
     pub fn poll(&mut self) -> Result<(), FFIErrorKind> {
+        let span = self.span.clone();
+        let _entered = span.enter();
+
         let _ = self.handle_connection_events();
 
         let mut poll_again = self.handle_timer();
         let _ = self.handle_endpoint_events();
         self.handle_app_events();
+        self.open_deferred_streams();
+        self.push_readable_streams();
         poll_again |= self.handle_transmits()?;
 
+        if self.qlog.is_some() {
+            let stats = self.stats();
+            self.with_qlog(|qlog| qlog.stats_updated(stats));
+        }
+
         Ok(())
     }
 
+    /// Returns the span carrying the connection id, remote address and side, a child of the endpoint span.
+    pub fn span(&self) -> &Span {
+        &self.span
+    }
+
+    /// Starts writing the qlog trace of the connection to the given sink.
+    pub fn set_qlog(&mut self, sink: &QlogSink, endpoint_id: u8) -> Result<(), FFIErrorKind> {
+        self.qlog = Some(QlogWriter::new(
+            sink,
+            endpoint_id,
+            self.connection_id(),
+            self.side(),
+        )?);
+        Ok(())
+    }
+
+    fn with_qlog(&mut self, f: impl FnOnce(&mut QlogWriter)) {
+        if let Some(qlog) = self.qlog.as_mut() {
+            f(qlog);
+        }
+    }
+
     /// Marks the connection as pollable.
     /// Connection should be polled when IO operations are performed, and timeout happened.
     ///
@@ -101,6 +268,511 @@ impl ConnectionImpl {
             .close(Instant::now(), error_code, Bytes::copy_from_slice(reason));
     }
 
+    /// Closes the connection once the peer acknowledged all data of the streams finished with `finish_stream`.
+    ///
+    /// Closes immediately if no finished stream is waiting for acknowledgement.
+    pub fn close_gracefully(&mut self, error_code: VarInt, reason: &[u8]) {
+        self.graceful_close = Some((error_code, Bytes::copy_from_slice(reason)));
+        self.close_if_finishes_acked();
+    }
+
+    /// Finishes the send side of a stream.
+    ///
+    /// Bytes buffered by `write_all` are sent before the stream is finished.
+    /// The `on_stream_acked` callback is invoked once the peer acknowledged all data of the stream, or stopped it.
+    pub fn finish_stream(&mut self, stream_id: StreamId) -> Result<(), FFIErrorKind> {
+        if self.write_queues.contains_key(&stream_id) {
+            self.deferred_finishes.insert(stream_id);
+        } else {
+            self.inner.send_stream(stream_id).finish()?;
+        }
+
+        self.unacked_finishes.insert(stream_id);
+        self.update_stream_stats(stream_id, |stats| stats.finished = true);
+        Ok(())
+    }
+
+    /// Closes the connection if a graceful close is pending and all finished streams are acknowledged.
+    fn close_if_finishes_acked(&mut self) {
+        if !self.unacked_finishes.is_empty() {
+            return;
+        }
+
+        if let Some((error_code, reason)) = self.graceful_close.take() {
+            self.inner.close(Instant::now(), error_code, reason);
+        }
+    }
+
+    /// Returns the negotiated ALPN protocol and the SNI server name.
+    ///
+    /// Returns `None` when the handshake data is not yet available.
+    pub fn handshake_data(&self) -> Option<HandshakeData> {
+        self.inner
+            .crypto_session()
+            .handshake_data()
+            .and_then(|data| data.downcast::<HandshakeData>().ok())
+            .map(|data| *data)
+    }
+
+    /// Sets the send priority that newly opened and accepted streams of the given direction start with.
+    pub fn set_default_stream_priority(&mut self, dir: Dir, priority: i32) {
+        self.default_stream_priorities[dir as usize] = priority;
+    }
+
+    /// Queues a request to open a stream, it is opened by the next poll that the stream limit of the direction allows.
+    ///
+    /// Requests are served in order, the `on_stream_opened` callback reports the opened stream with the given token.
+    /// Token 0 is reserved for streams opened by the peer.
+    /// Requests still pending when the connection is lost are reported with the `on_stream_open_failed` callback.
+    pub fn open_stream_deferred(&mut self, dir: Dir, token: u64) -> Result<(), FFIErrorKind> {
+        if token == 0 {
+            return Err(FFIErrorKind::invalid_argument(
+                "Token 0 is reserved for streams opened by the peer",
+            ));
+        }
+
+        if self.inner.is_closed() {
+            return Err(FFIErrorKind::error(
+                ErrorCategory::Connect,
+                "The connection is closed",
+            ));
+        }
+
+        self.deferred_opens[dir as usize].push_back(token);
+        Ok(())
+    }
+
+    /// Fails the requests queued with `open_stream_deferred`, the connection can no longer open streams.
+    fn fail_deferred_opens(&mut self) {
+        for dir in [Dir::Bi, Dir::Uni] {
+            for token in mem::take(&mut self.deferred_opens[dir as usize]) {
+                callbacks::on_stream_open_failed(self.connection_id(), dir as u8, token);
+            }
+        }
+    }
+
+    /// Opens the streams requested with `open_stream_deferred` as far as the stream limits allow.
+    fn open_deferred_streams(&mut self) {
+        for dir in [Dir::Bi, Dir::Uni] {
+            while let Some(&token) = self.deferred_opens[dir as usize].front() {
+                let stream_id = match self.inner.streams().open(dir) {
+                    Some(stream_id) => stream_id,
+                    None => break,
+                };
+
+                self.deferred_opens[dir as usize].pop_front();
+                self.register_stream(stream_id);
+
+                callbacks::on_stream_opened(
+                    self.connection_id(),
+                    self.stream_descriptor(stream_id),
+                    token,
+                );
+            }
+        }
+    }
+
+    /// Prepares a newly opened or accepted stream, applying the default send priority and tracking its statistics.
+    ///
+    /// The priority of streams without a send side, opened by the peer, is ignored.
+    pub fn register_stream(&mut self, stream_id: StreamId) {
+        let priority = self.default_stream_priorities[stream_id.dir() as usize];
+
+        if priority != 0 {
+            let _ = self.inner.send_stream(stream_id).set_priority(priority);
+        }
+
+        self.stream_stats.insert(stream_id, StreamStats::default());
+        self.with_qlog(|qlog| qlog.stream_state_updated(stream_id, "open"));
+    }
+
+    /// Returns the statistics of a stream.
+    ///
+    /// Statistics are available from when the stream is opened or accepted,
+    /// the final statistics of the last `MAX_CLOSED_STREAM_STATS` streams whose sides are both closed are kept.
+    pub fn stream_stats(&self, stream_id: StreamId) -> Result<StreamStats, FFIErrorKind> {
+        let mut stats = self
+            .stream_stats
+            .get(&stream_id)
+            .or_else(|| {
+                self.closed_stream_stats
+                    .iter()
+                    .find(|(id, _)| *id == stream_id)
+                    .map(|(_, stats)| stats)
+            })
+            .copied()
+            .ok_or_else(|| FFIErrorKind::error(ErrorCategory::Handle, "Unknown stream"))?;
+
+        stats.bytes_buffered = self.queued_bytes(stream_id) as u64;
+        Ok(stats)
+    }
+
+    /// Counts bytes read from a stream, `end` is the status if the end of the receive side was reached.
+    ///
+    /// Invokes the `on_stream_finished` callback once all data of a stream finished by the peer is read.
+    pub fn record_read(
+        &mut self,
+        stream_id: StreamId,
+        bytes: usize,
+        end: Option<StreamReadStatus>,
+    ) {
+        if end == Some(StreamReadStatus::Finished) {
+            callbacks::on_stream_finished(self.connection_id(), self.stream_descriptor(stream_id));
+        }
+
+        self.update_stream_stats(stream_id, |stats| {
+            stats.bytes_received += bytes as u64;
+            match end {
+                Some(StreamReadStatus::Finished) => stats.received_fin = true,
+                Some(StreamReadStatus::Reset) => stats.reset = true,
+                _ => {}
+            }
+        });
+    }
+
+    /// Counts bytes written to a stream, `blocked` is whether flow control limited the write.
+    fn record_written(&mut self, stream_id: StreamId, bytes: usize, blocked: bool) {
+        self.update_stream_stats(stream_id, |stats| {
+            stats.bytes_written += bytes as u64;
+            stats.write_blocked = blocked;
+        });
+    }
+
+    /// Updates the statistics of a tracked stream, they are moved to the closed streams once both sides of the stream are closed.
+    fn update_stream_stats(&mut self, stream_id: StreamId, f: impl FnOnce(&mut StreamStats)) {
+        let stats = match self.stream_stats.get_mut(&stream_id) {
+            Some(stats) => stats,
+            None => return,
+        };
+
+        f(stats);
+
+        let local = stream_id.initiator() == self.inner.side();
+        let bidi = stream_id.dir() == Dir::Bi;
+
+        if stats.is_closed(bidi || local, bidi || !local) {
+            let stats = self.stream_stats.remove(&stream_id).unwrap();
+
+            if self.closed_stream_stats.len() == MAX_CLOSED_STREAM_STATS {
+                self.closed_stream_stats.pop_front();
+            }
+            self.closed_stream_stats.push_back((stream_id, stats));
+        }
+    }
+
+    /// Reads as many consecutive chunks from a stream as fit in `buf`.
+    ///
+    /// Returns the number of bytes read and whether more data can be read.
+    /// The connection is marked pollable once when flow control credit has to be sent to the peer.
+    pub fn read_stream_chunks(
+        &mut self,
+        stream_id: StreamId,
+        buf: &mut [u8],
+    ) -> Result<(usize, StreamReadStatus), FFIErrorKind> {
+        let mut stream = self.inner.recv_stream(stream_id);
+        let mut chunks = stream.read(true)?;
+
+        let mut read = 0;
+        let result = loop {
+            if read == buf.len() {
+                break Ok(StreamReadStatus::Readable);
+            }
+
+            match chunks.next(buf.len() - read) {
+                Ok(Some(chunk)) => {
+                    buf[read..read + chunk.bytes.len()].copy_from_slice(&chunk.bytes);
+                    read += chunk.bytes.len();
+                }
+                Ok(None) => break Ok(StreamReadStatus::Finished),
+                Err(ReadError::Blocked) if read > 0 => break Ok(StreamReadStatus::Blocked),
+                Err(ReadError::Reset(_)) if read > 0 => break Ok(StreamReadStatus::Reset),
+                Err(e) => break Err(e),
+            }
+        };
+
+        let should_transmit = chunks.finalize().should_transmit();
+
+        let end = match result {
+            Ok(StreamReadStatus::Finished) => Some(StreamReadStatus::Finished),
+            Ok(StreamReadStatus::Reset) | Err(ReadError::Reset(_)) => Some(StreamReadStatus::Reset),
+            _ => None,
+        };
+        self.record_read(stream_id, read, end);
+
+        if should_transmit {
+            self.mark_pollable()?;
+        }
+
+        match result {
+            Ok(status) => Ok((read, status)),
+            Err(ReadError::Blocked) => {
+                Err(FFIErrorKind::FFIResultKind(FFIResultKind::BufferBlocked))
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Writes the slices to a stream in order, returns the number of bytes written.
+    ///
+    /// Stops at the first slice flow control does not allow to be written completely.
+    /// Returns `WouldBlock` if nothing can be written or bytes buffered by `write_all` are still pending for the stream.
+    pub fn write_vectored(
+        &mut self,
+        stream_id: StreamId,
+        slices: &[&[u8]],
+    ) -> Result<usize, FFIErrorKind> {
+        if self.write_queues.contains_key(&stream_id) {
+            return Err(FFIErrorKind::FFIResultKind(FFIResultKind::WouldBlock));
+        }
+
+        // Slices are written one by one, quinn copies only the bytes flow control accepts.
+        let mut stream = self.inner.send_stream(stream_id);
+        let mut written = 0;
+        let mut blocked = false;
+
+        for slice in slices {
+            match stream.write(slice) {
+                Ok(bytes) => {
+                    written += bytes;
+                    if bytes < slice.len() {
+                        blocked = true;
+                        break;
+                    }
+                }
+                Err(WriteError::Blocked) => {
+                    blocked = true;
+                    break;
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        self.record_written(stream_id, written, blocked);
+
+        if written == 0 && blocked {
+            return Err(FFIErrorKind::FFIResultKind(FFIResultKind::WouldBlock));
+        }
+
+        Ok(written)
+    }
+
+    /// Writes all bytes to a stream, buffering the bytes flow control does not allow to be sent yet.
+    ///
+    /// Buffered bytes are sent when the stream becomes writable, the `on_stream_drained` callback is invoked once all are sent.
+    /// Returns `WouldBlock` without writing if the bytes would exceed the write buffer limit of the connection.
+    pub fn write_all(&mut self, stream_id: StreamId, data: &[u8]) -> Result<(), FFIErrorKind> {
+        self.check_write_buffer_limit(data.len())?;
+
+        // Bytes queued earlier have to be sent first.
+        let written = if self.write_queues.contains_key(&stream_id) {
+            0
+        } else {
+            match self.inner.send_stream(stream_id).write(data) {
+                Ok(written) => written,
+                Err(WriteError::Blocked) => 0,
+                Err(e) => return Err(e.into()),
+            }
+        };
+        self.record_written(stream_id, written, written < data.len());
+
+        if written < data.len() {
+            self.write_all_chunks(stream_id, vec![Bytes::copy_from_slice(&data[written..])])?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes all chunks to a stream like `write_all`, without copying the chunks.
+    pub fn write_all_chunks(
+        &mut self,
+        stream_id: StreamId,
+        chunks: Vec<Bytes>,
+    ) -> Result<(), FFIErrorKind> {
+        let len = chunks.iter().map(Bytes::len).sum();
+        self.check_write_buffer_limit(len)?;
+
+        self.queued_bytes += len;
+        self.write_queues
+            .entry(stream_id)
+            .or_default()
+            .extend(chunks);
+
+        // Writes until blocked so the stream emits a writable event once flow control allows more data.
+        self.flush_write_queue(stream_id)?;
+
+        Ok(())
+    }
+
+    /// Returns `WouldBlock` if buffering `len` more bytes would exceed the write buffer limit.
+    pub fn check_write_buffer_limit(&self, len: usize) -> Result<(), FFIErrorKind> {
+        if self.queued_bytes + len > self.write_buffer_limit {
+            return Err(FFIErrorKind::FFIResultKind(FFIResultKind::WouldBlock));
+        }
+
+        Ok(())
+    }
+
+    /// Sets the maximum number of bytes the connection buffers for `write_all` across all streams.
+    pub fn set_write_buffer_limit(&mut self, limit: usize) {
+        self.write_buffer_limit = limit;
+    }
+
+    /// Returns the number of bytes buffered by `write_all` that are not yet written to the stream.
+    pub fn queued_bytes(&self, stream_id: StreamId) -> usize {
+        self.write_queues
+            .get(&stream_id)
+            .map_or(0, |queue| queue.iter().map(Bytes::len).sum())
+    }
+
+    /// Writes the buffered bytes of a stream, returns whether all buffered bytes are written.
+    fn flush_write_queue(&mut self, stream_id: StreamId) -> Result<bool, FFIErrorKind> {
+        let queue = match self.write_queues.get_mut(&stream_id) {
+            Some(queue) => queue,
+            None => return Ok(true),
+        };
+
+        let mut stream = self.inner.send_stream(stream_id);
+        let mut written_bytes = 0;
+
+        let result = loop {
+            if queue.is_empty() {
+                break Ok(true);
+            }
+
+            match stream.write_chunks(queue.make_contiguous()) {
+                Ok(written) => {
+                    queue.drain(..written.chunks);
+                    self.queued_bytes -= written.bytes;
+                    written_bytes += written.bytes;
+
+                    if written.bytes == 0 {
+                        break Ok(false);
+                    }
+                }
+                Err(WriteError::Blocked) => break Ok(false),
+                Err(e) => break Err(e),
+            }
+        };
+
+        self.record_written(stream_id, written_bytes, matches!(result, Ok(false)));
+
+        match result {
+            Ok(true) => {}
+            Ok(false) => return Ok(false),
+            Err(e) => {
+                self.discard_write_queue(stream_id);
+                return Err(e.into());
+            }
+        }
+
+        self.write_queues.remove(&stream_id);
+
+        if self.deferred_finishes.remove(&stream_id) {
+            if let Err(e) = self.inner.send_stream(stream_id).finish() {
+                self.unacked_finishes.remove(&stream_id);
+                self.close_if_finishes_acked();
+                return Err(e.into());
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Drops the buffered bytes of a stream that can no longer be written to.
+    fn discard_write_queue(&mut self, stream_id: StreamId) {
+        if let Some(queue) = self.write_queues.remove(&stream_id) {
+            self.queued_bytes -= queue.iter().map(Bytes::len).sum::<usize>();
+        }
+
+        // A stream that can no longer be written to is never acknowledged.
+        self.deferred_finishes.remove(&stream_id);
+        if self.unacked_finishes.remove(&stream_id) {
+            self.close_if_finishes_acked();
+        }
+    }
+
+    /// Enables or disables pushing stream data to the client application.
+    ///
+    /// When enabled, readable streams are read after each poll and their data is delivered with the `on_stream_data` callback
+    /// instead of invoking `on_stream_readable`. Returns `InvalidArgument` when enabling while `on_stream_data` is not set.
+    pub fn set_push_stream_data(&mut self, enabled: bool) -> Result<(), FFIErrorKind> {
+        if enabled && !callbacks::has_on_stream_data() {
+            return Err(FFIErrorKind::invalid_argument(
+                "Push mode requires the on_stream_data callback",
+            ));
+        }
+
+        self.push_stream_data = enabled;
+        Ok(())
+    }
+
+    /// Returns the state of the 0-RTT attempt of an outgoing connection.
+    pub fn zero_rtt_state(&self) -> ZeroRttState {
+        if !self.zero_rtt_attempted {
+            ZeroRttState::NotAttempted
+        } else if self.inner.is_handshaking() {
+            ZeroRttState::Pending
+        } else if self.inner.accepted_0rtt() {
+            ZeroRttState::Accepted
+        } else {
+            ZeroRttState::Rejected
+        }
+    }
+
+    /// Reads all streams that became readable and delivers their data with the `on_stream_data` callback.
+    ///
+    /// Streams queued while push mode was enabled are reported with `on_stream_readable` once it is disabled.
+    fn push_readable_streams(&mut self) {
+        let connection_id = self.connection_id();
+
+        if !self.push_stream_data {
+            for stream_id in mem::take(&mut self.readable_streams) {
+                callbacks::on_stream_readable(connection_id, self.stream_descriptor(stream_id));
+            }
+            return;
+        }
+
+        for stream_id in mem::take(&mut self.readable_streams) {
+            let stream = self.stream_descriptor(stream_id);
+            let mut recv_stream = self.inner.recv_stream(stream_id);
+            let mut chunks = match recv_stream.read(true) {
+                Ok(chunks) => chunks,
+                Err(e) => {
+                    tracing::debug!("Stream {} is not readable: {}", stream_id, e);
+                    continue;
+                }
+            };
+
+            let mut read = 0;
+            let status = loop {
+                match chunks.next(usize::MAX) {
+                    Ok(Some(chunk)) => {
+                        read += chunk.bytes.len();
+                        callbacks::on_stream_data(
+                            connection_id,
+                            stream,
+                            chunk.bytes.as_ptr(),
+                            chunk.bytes.len() as u32,
+                            StreamReadStatus::Readable,
+                        )
+                    }
+                    Ok(None) => break Some(StreamReadStatus::Finished),
+                    Err(ReadError::Blocked) => break None,
+                    Err(ReadError::Reset(_)) => break Some(StreamReadStatus::Reset),
+                }
+            };
+
+            // Flow control credit is sent by the transmits handled after this.
+            let _ = chunks.finalize();
+
+            if let Some(status) = status {
+                callbacks::on_stream_data(connection_id, stream, ptr::null(), 0, status);
+            }
+
+            self.record_read(stream_id, read, status);
+        }
+    }
+
     fn handle_timer(&mut self) -> bool {
         match self.inner.poll_timeout() {
             Some(deadline) => {
@@ -126,6 +798,7 @@ impl ConnectionImpl {
     fn handle_transmits(&mut self) -> Result<bool, FFIErrorKind> {
         let mut should_notify = false;
         while let Some(t) = self.inner.poll_transmit(Instant::now(), 1) {
+            self.with_qlog(|qlog| qlog.datagram_sent(t.contents.len()));
             self.endpoint_events
                 .send((self.connection_handle, EndpointEvent::Transmit(t)))?;
             should_notify = true;
@@ -167,13 +840,47 @@ impl ConnectionImpl {
             use quinn_proto::Event::*;
             match event {
                 HandshakeDataReady => {
-                    // ignore for now
+                    if let Some(data) = self.handshake_data() {
+                        let protocol = data.protocol.unwrap_or_default();
+                        let server_name = data.server_name.unwrap_or_default();
+
+                        callbacks::on_handshake_data_ready(
+                            self.connection_id(),
+                            protocol.as_ptr(),
+                            protocol.len() as u32,
+                            server_name.as_ptr(),
+                            server_name.len() as u32,
+                        );
+                    }
+                }
+                Connected => {
+                    self.handshake_completed = true;
+                    self.with_qlog(|qlog| {
+                        qlog.connection_state_updated("handshake_confirmed", None)
+                    });
+                    callbacks::on_connected(self.connection_id());
+
+                    if self.zero_rtt_attempted {
+                        callbacks::on_zero_rtt_resolved(
+                            self.connection_id(),
+                            self.zero_rtt_state() as u8,
+                        );
+                    }
                 }
-                Connected => callbacks::on_connected(self.connection_id()),
                 ConnectionLost { reason } => {
                     // TODO: self.terminate(reason);
 
+                    if !self.handshake_completed {
+                        let _ = self.endpoint_events.send((
+                            self.connection_handle,
+                            EndpointEvent::HandshakeFailed(reason.clone()),
+                        ));
+                    }
+
+                    self.fail_deferred_opens();
+
                     let reason = format!("{:?}", reason);
+                    self.with_qlog(|qlog| qlog.connection_state_updated("closed", Some(&reason)));
                     let bytes = reason.as_bytes();
 
                     callbacks::on_connection_lost(
@@ -183,14 +890,39 @@ impl ConnectionImpl {
                     );
                 }
                 Stream(StreamEvent::Writable { id }) => {
-                    callbacks::on_stream_writable(self.connection_id(), id)
+                    self.update_stream_stats(id, |stats| stats.write_blocked = false);
+
+                    if self.write_queues.contains_key(&id) {
+                        match self.flush_write_queue(id) {
+                            Ok(true) => callbacks::on_stream_drained(
+                                self.connection_id(),
+                                self.stream_descriptor(id),
+                            ),
+                            Ok(false) => {}
+                            Err(e) => {
+                                tracing::debug!("Dropped buffered bytes of stream {}: {}", id, e)
+                            }
+                        }
+                    } else {
+                        callbacks::on_stream_writable(
+                            self.connection_id(),
+                            self.stream_descriptor(id),
+                        )
+                    }
                 }
                 Stream(StreamEvent::Opened { dir }) => {
                     if let Some(stream_id) = self.inner.streams().accept(dir) {
+                        self.register_stream(stream_id);
+
+                        // Data on a stream newly opened by the peer does not trigger a readable event.
+                        if self.push_stream_data {
+                            self.readable_streams.push(stream_id);
+                        }
+
                         callbacks::on_stream_opened(
                             self.connection_id(),
-                            VarInt::from(stream_id).into_inner(),
-                            dir as u8,
+                            self.stream_descriptor(stream_id),
+                            0,
                         );
                     }
                 }
@@ -198,21 +930,62 @@ impl ConnectionImpl {
                     callbacks::on_datagram_received(self.connection_id());
                 }
                 Stream(StreamEvent::Readable { id }) => {
-                    callbacks::on_stream_readable(self.connection_id(), id);
+                    if self.push_stream_data {
+                        if !self.readable_streams.contains(&id) {
+                            self.readable_streams.push(id);
+                        }
+                    } else {
+                        callbacks::on_stream_readable(
+                            self.connection_id(),
+                            self.stream_descriptor(id),
+                        );
+                    }
                 }
                 Stream(StreamEvent::Available { dir }) => {
                     callbacks::on_stream_available(self.connection_id(), dir as u8);
                 }
+                // Only send sides are reported finished, once acknowledged or stopped. Reads report finished receive sides.
                 Stream(StreamEvent::Finished { id }) => {
-                    callbacks::on_stream_finished(self.connection_id(), id);
+                    callbacks::on_stream_acked(self.connection_id(), self.stream_descriptor(id));
+
+                    self.with_qlog(|qlog| qlog.stream_state_updated(id, "data_acked"));
+
+                    self.update_stream_stats(id, |stats| stats.acked = true);
+
+                    if self.unacked_finishes.remove(&id) {
+                        self.close_if_finishes_acked();
+                    }
                 }
                 Stream(StreamEvent::Stopped { id, error_code: _ }) => {
-                    callbacks::on_stream_stopped(self.connection_id(), id);
+                    self.discard_write_queue(id);
+                    self.update_stream_stats(id, |stats| stats.stopped = true);
+                    self.with_qlog(|qlog| qlog.stream_state_updated(id, "stop_sending_received"));
+                    callbacks::on_stream_stopped(self.connection_id(), self.stream_descriptor(id));
                 }
             }
         }
     }
 
+    /// Returns the statistics of the connection.
+    pub fn stats(&self) -> ConnectionStats {
+        ConnectionStats::from(&self.inner)
+    }
+
+    /// Returns the side of the connection.
+    pub fn side(&self) -> ConnectionSide {
+        self.inner.side().into()
+    }
+
+    /// Returns the descriptor of a stream that is passed to the stream callbacks.
+    pub fn stream_descriptor(&self, stream_id: StreamId) -> StreamDescriptor {
+        StreamDescriptor {
+            id: VarInt::from(stream_id).into_inner(),
+            dir: stream_id.dir() as u8,
+            initiator: stream_id.initiator().into(),
+            locally_opened: stream_id.initiator() == self.inner.side(),
+        }
+    }
+
     fn connection_id(&self) -> u32 {
         return self.connection_handle.0 as u32;
     }
@@ -7,14 +7,24 @@ use crate::{
     },
 };
 
-use quinn_proto::Transmit;
+use bytes::BytesMut;
+use quinn_proto::{
+    DatagramEvent,
+    Transmit,
+};
+use tracing::Span;
 
 use crate::{
     proto::{
         ClientConfig,
         ConnectError,
     },
-    proto_impl::FFIErrorKind,
+    proto_impl::{
+        ConnectionStats,
+        EndpointStats,
+        FFIErrorKind,
+        QlogSink,
+    },
 };
 use std::{
     collections::HashMap,
@@ -29,6 +39,10 @@ use std::{
         Mutex,
     },
     thread,
+    time::{
+        Duration,
+        Instant,
+    },
 };
 
 use crate::proto::ConnectionHandle;
@@ -41,8 +55,16 @@ use std::sync::TryLockError;
 const IO_LOOP_BOUND: usize = 160;
 
 /// An endpoint id that is increased for each created endpoint.
+///
+/// The id is passed to the host as a `u8` and wraps after 255 endpoints,
+/// an id is only unique among the endpoints alive at the same time if fewer than 256 endpoints are created in between.
 static ENDPOINT_ID: AtomicU8 = AtomicU8::new(0);
 
+/// Allocates the id of a new endpoint, starting at 1.
+fn next_endpoint_id() -> u8 {
+    ENDPOINT_ID.fetch_add(1, Ordering::Relaxed).wrapping_add(1)
+}
+
 /// Events for the endpoint.
 #[derive(Debug)]
 pub enum EndpointEvent {
@@ -50,6 +72,8 @@ pub enum EndpointEvent {
     Proto(proto::EndpointEvent),
     /// Transmit ready.
     Transmit(proto::Transmit),
+    /// The connection was lost before the handshake completed.
+    HandshakeFailed(proto::ConnectionError),
 }
 
 /// Polls the endpoint when notified to do so.
@@ -58,6 +82,8 @@ pub struct EndpointPoller {
     receiver: mpsc::Receiver<i8>,
     loop_again: bool,
     endpoint_ref: Arc<Mutex<EndpointImpl>>,
+    // Time until the endpoint reports connection statistics, polls without notification when elapsed.
+    stats_timeout: Option<Duration>,
 }
 
 impl EndpointPoller {
@@ -69,6 +95,7 @@ impl EndpointPoller {
                 endpoint_ref,
                 receiver,
                 loop_again: false,
+                stats_timeout: None,
             },
             sender,
         )
@@ -81,9 +108,15 @@ impl EndpointPoller {
             let mut spin_counter = 0;
             loop {
                 if !self.loop_again {
-                    let code = self.receiver.recv();
-
-                    if Ok(-1) == code {
+                    let code = match self.stats_timeout {
+                        Some(timeout) => match self.receiver.recv_timeout(timeout) {
+                            Err(mpsc::RecvTimeoutError::Timeout) => Some(0),
+                            code => code.ok(),
+                        },
+                        None => self.receiver.recv().ok(),
+                    };
+
+                    if Some(-1) == code {
                         // exit this poll operation, endpoint sent exit code.
                         return;
                     }
@@ -104,6 +137,7 @@ impl EndpointPoller {
                         Ok(mut e) => {
                             spin_counter = 0;
                             e.poll().expect("Endpoint polling thread panicked!");
+                            self.stats_timeout = e.stats_timeout();
                             self.loop_again = false;
                         }
                         _ => {}
@@ -128,13 +162,18 @@ pub struct EndpointImpl {
     // Locking a connection could result in deadlocks if the application is already using the lock.
     // TODO: remove this, currently required in handle_datagram
     connection_refs: HashMap<proto::ConnectionHandle, Arc<Mutex<ConnectionImpl>>>,
+    stats_interval: Option<Duration>,
+    next_stats_report: Instant,
+    stats: EndpointStats,
+    qlog: Option<QlogSink>,
+    span: Span,
 }
 
 impl EndpointImpl {
     pub fn new(endpoint: proto::Endpoint) -> Self {
         let (tx, rx) = mpsc::channel();
 
-        let id = ENDPOINT_ID.load(Ordering::Relaxed).wrapping_add(1);
+        let id = next_endpoint_id();
 
         return EndpointImpl {
             inner: endpoint,
@@ -145,9 +184,19 @@ impl EndpointImpl {
             id,
             default_client_config: None,
             connection_refs: HashMap::new(),
+            stats_interval: None,
+            next_stats_report: Instant::now(),
+            stats: EndpointStats::default(),
+            qlog: None,
+            span: tracing::info_span!("endpoint", id),
         };
     }
 
+    /// Returns the span carrying the endpoint id, the parent of the spans of its connections.
+    pub fn span(&self) -> &Span {
+        &self.span
+    }
+
     /// Sets the endpoint poll notifier.
     /// This sender can be used to trigger a endpoint poll operation.
     pub fn set_poll_notifier(&mut self, notifer: mpsc::Sender<i8>) {
@@ -159,6 +208,9 @@ impl EndpointImpl {
     /// - Triggers a callback for all outgoing transmits.
     /// - Handles all connection sent endpoint events.
     pub fn poll(&mut self) -> Result<bool, FFIErrorKind> {
+        let span = self.span.clone();
+        let _entered = span.enter();
+
         while let Some(transmit) = self.inner.poll_transmit() {
             // TODO: batch transmits
             self.notify_transmit(transmit);
@@ -166,7 +218,126 @@ impl EndpointImpl {
 
         // TODO limit max outgoing, invoke callback to poll again.
 
-        self.handle_connection_events()
+        let poll_again = self.handle_connection_events()?;
+        self.report_connection_stats();
+
+        Ok(poll_again)
+    }
+
+    /// Sets the interval at which the statistics of all connections are reported with the `on_connection_stats` callback.
+    ///
+    /// `None` disables reporting. Statistics are reported by the first endpoint poll after the interval elapsed.
+    pub fn set_stats_interval(&mut self, interval: Option<Duration>) {
+        self.stats_interval = interval;
+        self.next_stats_report = Instant::now() + interval.unwrap_or_default();
+
+        // Wakes the polling thread so it picks up the new interval.
+        if let Some(notifier) = &self.endpoint_poll_notifier {
+            let _ = notifier.send(0);
+        }
+    }
+
+    /// Sets where the qlog traces of connections are written to, `None` disables qlog output.
+    ///
+    /// Only applies to connections created afterwards.
+    pub fn set_qlog(&mut self, sink: Option<QlogSink>) {
+        self.qlog = sink;
+    }
+
+    /// Returns the time until the next connection statistics report is due, if reporting is enabled.
+    pub fn stats_timeout(&self) -> Option<Duration> {
+        self.stats_interval.map(|_| {
+            self.next_stats_report
+                .saturating_duration_since(Instant::now())
+        })
+    }
+
+    /// Returns the statistics of all connections by connection id.
+    ///
+    /// Connections locked by the client application at that moment are skipped.
+    pub fn connection_stats(&self) -> Vec<(u32, ConnectionStats)> {
+        self.connection_refs
+            .iter()
+            .filter_map(|(handle, connection)| {
+                let connection = connection.try_lock().ok()?;
+                Some((handle.0 as u32, connection.stats()))
+            })
+            .collect()
+    }
+
+    /// Reports the statistics of all connections in a single callback when the stats interval elapsed.
+    ///
+    /// Connections locked by the client application at that moment are skipped.
+    fn report_connection_stats(&mut self) {
+        let interval = match self.stats_interval {
+            Some(interval) => interval,
+            None => return,
+        };
+
+        let now = Instant::now();
+        if now < self.next_stats_report {
+            return;
+        }
+        self.next_stats_report = now + interval;
+
+        let (connection_ids, stats): (Vec<_>, Vec<_>) = self.connection_stats().into_iter().unzip();
+
+        if !stats.is_empty() {
+            callbacks::on_connection_stats(
+                self.id,
+                connection_ids.as_ptr(),
+                stats.as_ptr(),
+                stats.len() as u32,
+            );
+        }
+    }
+
+    /// Processes a datagram received from the given address, counting it in the endpoint statistics.
+    pub fn handle_datagram(
+        &mut self,
+        addr: SocketAddr,
+        data: &[u8],
+    ) -> Option<(ConnectionHandle, DatagramEvent)> {
+        self.stats.datagrams_received += 1;
+        self.stats.bytes_received += data.len() as u64;
+
+        // Sends the responses to earlier datagrams, so a transmit queued by this datagram is known to answer it.
+        while let Some(transmit) = self.inner.poll_transmit() {
+            self.notify_transmit(transmit);
+        }
+
+        let event = self
+            .inner
+            .handle(Instant::now(), addr, None, None, BytesMut::from(data));
+
+        match &event {
+            Some((_, DatagramEvent::NewConnection(_))) => self.stats.connections_accepted += 1,
+            Some((_, DatagramEvent::ConnectionEvent(_))) => {}
+            None => {
+                self.stats.datagrams_dropped += 1;
+
+                // An initial packet is answered without a connection when the handshake is refused or retried,
+                // or with a version negotiation packet when its version is not supported.
+                if is_initial_packet(data) {
+                    if let Some(transmit) = self.inner.poll_transmit() {
+                        if is_handshake_rejection(&transmit.contents) {
+                            self.stats.connections_rejected += 1;
+                        }
+                        self.notify_transmit(transmit);
+                    }
+                }
+            }
+        }
+
+        event
+    }
+
+    /// Returns a snapshot of the endpoint statistics.
+    pub fn stats(&self) -> EndpointStats {
+        EndpointStats {
+            active_connections: self.connections.len() as u64,
+            ..self.stats
+        }
     }
 
     /// Creates and adds a connection for this endpoint.
@@ -178,13 +349,30 @@ impl EndpointImpl {
         let (send, recv) = mpsc::channel();
         let _ = self.connections.insert(handle, send);
 
-        ConnectionImpl::new(
+        let span = tracing::info_span!(
+            parent: &self.span,
+            "connection",
+            id = handle.0,
+            remote = %connection.remote_address(),
+            side = ?connection.side()
+        );
+
+        let mut connection = ConnectionImpl::new(
             connection,
             handle,
             recv,
             self.endpoint_events_tx.clone(),
             self.endpoint_poll_notifier.clone(),
-        )
+            span,
+        );
+
+        if let Some(sink) = &self.qlog {
+            if let Err(e) = connection.set_qlog(sink, self.id) {
+                tracing::warn!("Failed to start qlog trace: {:?}", e);
+            }
+        }
+
+        connection
     }
 
     /// Removes, not closing, the connection from the endpoint.
@@ -225,7 +413,7 @@ impl EndpointImpl {
     ) -> Result<(), FFIErrorKind> {
         self.connections
             .get_mut(&handle)
-            .unwrap()
+            .ok_or(FFIErrorKind::UnknownConnection)?
             .send(ConnectionEvent::Proto(event))?;
 
         Ok(())
@@ -269,6 +457,7 @@ impl EndpointImpl {
         server_name: &str,
     ) -> Result<ConnectionImpl, ConnectError> {
         let (ch, conn) = self.inner.connect(config, addr, server_name)?;
+        self.stats.connections_initiated += 1;
 
         Ok(self.add_connection(ch, conn))
     }
@@ -299,15 +488,17 @@ impl EndpointImpl {
 
                             if let Some(event) = self.inner.handle_event(handle, proto) {
                                 // Ignoring errors from dropped connections that haven't yet been cleaned up
-                                self.connections
-                                    .get_mut(&handle)
-                                    .unwrap()
-                                    .send(ConnectionEvent::Proto(event))?;
+                                if let Some(connection) = self.connections.get_mut(&handle) {
+                                    let _ = connection.send(ConnectionEvent::Proto(event));
+                                }
                             }
                         }
                         EndpointEvent::Transmit(transmit) => {
                             self.notify_transmit(transmit);
                         }
+                        EndpointEvent::HandshakeFailed(reason) => {
+                            self.stats.handshake_failures.record(&reason);
+                        }
                     }
                 }
                 Err(_) => {
@@ -322,6 +513,26 @@ impl EndpointImpl {
 
     /// Invokes a initialized callback by the client application.
     fn notify_transmit(&mut self, transmit: Transmit) {
+        self.stats.transmits += 1;
+        self.stats.bytes_sent += transmit.contents.len() as u64;
+
         callbacks::on_transmit(self.id, transmit);
     }
 }
+
+/// Returns whether the datagram starts with a QUIC version 1 initial packet.
+fn is_initial_packet(data: &[u8]) -> bool {
+    // Long header form bit set and packet type 0.
+    matches!(data.first(), Some(byte) if byte & 0xb0 == 0x80)
+}
+
+/// Returns whether the datagram refuses a handshake with an initial packet carrying a close, or asks to retry it.
+fn is_handshake_rejection(data: &[u8]) -> bool {
+    // Version negotiation packets have version 0 and no packet type.
+    if data.len() < 5 || data[1..5] == [0; 4] {
+        return false;
+    }
+
+    // Long header form bit set and packet type 0 (initial) or 3 (retry).
+    matches!(data[0] & 0xb0, 0x80 | 0xb0)
+}
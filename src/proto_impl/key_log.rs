@@ -0,0 +1,70 @@
+use crate::{
+    ffi::callbacks,
+    proto_impl::FFIErrorKind,
+};
+use rustls::KeyLog;
+use std::{
+    fmt::Write as _,
+    fs::{
+        File,
+        OpenOptions,
+    },
+    io::Write,
+    path::Path,
+    sync::Mutex,
+};
+
+/// Writes TLS secrets in the NSS key log format to a file, like `SSLKEYLOGFILE`.
+pub struct FileKeyLog {
+    file: Mutex<File>,
+}
+
+impl FileKeyLog {
+    /// Opens the key log file, lines are appended when the file already exists.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, FFIErrorKind> {
+        let file = OpenOptions::new().append(true).create(true).open(path)?;
+
+        Ok(FileKeyLog {
+            file: Mutex::new(file),
+        })
+    }
+}
+
+impl KeyLog for FileKeyLog {
+    fn log(&self, label: &str, client_random: &[u8], secret: &[u8]) {
+        let line = format_line(label, client_random, secret);
+
+        if let Err(e) = self.file.lock().unwrap().write_all(line.as_bytes()) {
+            tracing::warn!("Failed to write key log line: {}", e);
+        }
+    }
+}
+
+/// Forwards TLS secrets in the NSS key log format to the `on_key_log` callback.
+pub struct CallbackKeyLog;
+
+impl KeyLog for CallbackKeyLog {
+    fn log(&self, label: &str, client_random: &[u8], secret: &[u8]) {
+        let line = format_line(label, client_random, secret);
+        callbacks::on_key_log(line.as_ptr(), line.len() as u32);
+    }
+}
+
+/// Formats a NSS key log line, terminated with a newline.
+fn format_line(label: &str, client_random: &[u8], secret: &[u8]) -> String {
+    let mut line =
+        String::with_capacity(label.len() + 2 * (client_random.len() + secret.len()) + 3);
+    line.push_str(label);
+    line.push(' ');
+    push_hex(&mut line, client_random);
+    line.push(' ');
+    push_hex(&mut line, secret);
+    line.push('\n');
+    line
+}
+
+fn push_hex(line: &mut String, bytes: &[u8]) {
+    for byte in bytes {
+        let _ = write!(line, "{:02x}", byte);
+    }
+}
@@ -0,0 +1,205 @@
+use crate::{
+    ffi::callbacks,
+    proto_impl::{
+        ErrorCategory,
+        FFIErrorKind,
+    },
+};
+use std::{
+    fmt,
+    fmt::Write as _,
+    sync::Mutex,
+};
+use tracing::{
+    field::{
+        Field,
+        Visit,
+    },
+    span,
+    Event,
+    Level,
+    Subscriber,
+};
+use tracing_subscriber::{
+    layer::Context,
+    prelude::*,
+    registry::LookupSpan,
+    reload,
+    EnvFilter,
+    Layer,
+    Registry,
+};
+
+/// Handle to the filter of the global subscriber, set once logging is enabled.
+static LOG_FILTER: Mutex<Option<reload::Handle<EnvFilter, Registry>>> = Mutex::new(None);
+
+/// Enables logging to the `on_log` callback with the given filter directives.
+///
+/// The global subscriber is installed on the first call, later calls replace the filter.
+pub fn enable_log(filter: &str) -> Result<(), FFIErrorKind> {
+    let filter = parse_filter(filter)?;
+    let mut handle = LOG_FILTER.lock().unwrap();
+
+    if let Some(handle) = handle.as_ref() {
+        return handle
+            .reload(filter)
+            .map_err(|e| FFIErrorKind::error(ErrorCategory::Internal, &e.to_string()));
+    }
+
+    let (filter, filter_handle) = reload::Layer::new(filter);
+    tracing::subscriber::set_global_default(Registry::default().with(filter).with(CallbackLayer))
+        .map_err(|_| FFIErrorKind::error(ErrorCategory::Config, "A global logger is already set"))?;
+
+    *handle = Some(filter_handle);
+    Ok(())
+}
+
+/// Replaces the filter directives of the logger enabled with `enable_log`.
+pub fn set_log_filter(filter: &str) -> Result<(), FFIErrorKind> {
+    let filter = parse_filter(filter)?;
+
+    match LOG_FILTER.lock().unwrap().as_ref() {
+        Some(handle) => handle
+            .reload(filter)
+            .map_err(|e| FFIErrorKind::error(ErrorCategory::Internal, &e.to_string())),
+        None => Err(FFIErrorKind::error(
+            ErrorCategory::Config,
+            "Logging is not enabled",
+        )),
+    }
+}
+
+fn parse_filter(filter: &str) -> Result<EnvFilter, FFIErrorKind> {
+    EnvFilter::try_new(filter)
+        .map_err(|e| FFIErrorKind::error(ErrorCategory::Config, &e.to_string()))
+}
+
+/// The level of a log record passed to the `on_log` callback.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogLevel {
+    Error = 0,
+    Warn = 1,
+    Info = 2,
+    Debug = 3,
+    Trace = 4,
+}
+
+impl From<&Level> for LogLevel {
+    fn from(level: &Level) -> Self {
+        match *level {
+            Level::ERROR => LogLevel::Error,
+            Level::WARN => LogLevel::Warn,
+            Level::INFO => LogLevel::Info,
+            Level::DEBUG => LogLevel::Debug,
+            Level::TRACE => LogLevel::Trace,
+        }
+    }
+}
+
+/// A `Layer` forwarding events to the `on_log` callback.
+///
+/// Each record carries the level, the target, the message with the other event fields appended as `key=value`,
+/// and the entered spans with their fields from the root span, formatted like `endpoint{id=1}:connection{id=2}`.
+pub struct CallbackLayer;
+
+/// The formatted fields of a span, stored in the span extensions.
+struct SpanFields(String);
+
+impl<S> Layer<S> for CallbackLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+        let span = ctx.span(id).expect("Span not found, this is a bug");
+
+        let mut fields = FieldVisitor::default();
+        attrs.record(&mut fields);
+        span.extensions_mut().insert(SpanFields(fields.fields));
+    }
+
+    fn on_record(&self, id: &span::Id, values: &span::Record<'_>, ctx: Context<'_, S>) {
+        let span = ctx.span(id).expect("Span not found, this is a bug");
+        let mut extensions = span.extensions_mut();
+
+        if let Some(SpanFields(fields)) = extensions.get_mut::<SpanFields>() {
+            let mut visitor = FieldVisitor {
+                fields: std::mem::take(fields),
+                ..FieldVisitor::default()
+            };
+            values.record(&mut visitor);
+            *fields = visitor.fields;
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let mut visitor = FieldVisitor::default();
+        event.record(&mut visitor);
+
+        let mut message = visitor.message;
+        if !visitor.fields.is_empty() {
+            if !message.is_empty() {
+                message.push(' ');
+            }
+            message.push_str(&visitor.fields);
+        }
+
+        let mut spans = String::new();
+        if let Some(scope) = ctx.event_scope(event) {
+            for span in scope.from_root() {
+                if !spans.is_empty() {
+                    spans.push(':');
+                }
+                spans.push_str(span.name());
+
+                if let Some(SpanFields(fields)) = span.extensions().get::<SpanFields>() {
+                    if !fields.is_empty() {
+                        let _ = write!(spans, "{{{}}}", fields);
+                    }
+                }
+            }
+        }
+
+        let metadata = event.metadata();
+        let target = metadata.target();
+
+        callbacks::on_log(
+            LogLevel::from(metadata.level()) as u8,
+            target.as_ptr(),
+            target.len() as u32,
+            message.as_ptr(),
+            message.len() as u32,
+            spans.as_ptr(),
+            spans.len() as u32,
+        );
+    }
+}
+
+/// Collects the `message` field and formats the other fields as space separated `key=value` pairs.
+#[derive(Default)]
+struct FieldVisitor {
+    message: String,
+    fields: String,
+}
+
+impl Visit for FieldVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message.push_str(value);
+        } else {
+            self.record_debug(field, &value);
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.message, "{:?}", value);
+            return;
+        }
+
+        if !self.fields.is_empty() {
+            self.fields.push(' ');
+        }
+        let _ = write!(self.fields, "{}={:?}", field.name(), value);
+    }
+}
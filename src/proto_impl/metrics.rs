@@ -0,0 +1,418 @@
+use crate::proto_impl::{
+    ConnectionStats,
+    EndpointImpl,
+    EndpointStats,
+};
+use std::{
+    cell::RefCell,
+    fmt::{
+        Display,
+        Write,
+    },
+    sync::{
+        Arc,
+        Mutex,
+        Weak,
+    },
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
+/// Endpoints whose metrics are rendered, dropped endpoints are removed when rendering.
+static ENDPOINTS: Mutex<Vec<RegisteredEndpoint>> = Mutex::new(Vec::new());
+
+/// How long metrics that did not fit in the buffer of the caller are kept for a retry.
+const PENDING_METRICS_LIFETIME: Duration = Duration::from_secs(1);
+
+thread_local!(
+    // Metrics that did not fit in the buffer of the caller and when they were rendered, returned again on retry.
+    static PENDING_METRICS: RefCell<Option<(String, Instant)>> = const { RefCell::new(None) };
+);
+
+/// An endpoint counter: metric name, help text and the counter value.
+type EndpointCounter = (&'static str, &'static str, fn(&EndpointStats) -> u64);
+
+/// A handshake failure reason: label value and the counter value.
+type HandshakeFailure = (&'static str, fn(&EndpointStats) -> u64);
+
+/// A connection metric: metric name, metric type, help text and the value.
+type ConnectionMetric = (
+    &'static str,
+    &'static str,
+    &'static str,
+    fn(&ConnectionStats) -> f64,
+);
+
+/// Counters of each endpoint.
+const ENDPOINT_COUNTERS: [EndpointCounter; 8] = [
+    (
+        "quinn_endpoint_connections_accepted_total",
+        "Incoming connections accepted.",
+        |s| s.connections_accepted,
+    ),
+    (
+        "quinn_endpoint_connections_initiated_total",
+        "Outgoing connections initiated.",
+        |s| s.connections_initiated,
+    ),
+    (
+        "quinn_endpoint_connections_rejected_total",
+        "Incoming handshakes refused or asked to retry.",
+        |s| s.connections_rejected,
+    ),
+    (
+        "quinn_endpoint_datagrams_received_total",
+        "Datagrams received.",
+        |s| s.datagrams_received,
+    ),
+    (
+        "quinn_endpoint_datagrams_dropped_total",
+        "Datagrams that did not belong to or create a connection.",
+        |s| s.datagrams_dropped,
+    ),
+    ("quinn_endpoint_transmits_total", "Transmits sent.", |s| {
+        s.transmits
+    }),
+    (
+        "quinn_endpoint_received_bytes_total",
+        "Bytes of the datagrams received.",
+        |s| s.bytes_received,
+    ),
+    (
+        "quinn_endpoint_sent_bytes_total",
+        "Bytes of the transmits sent.",
+        |s| s.bytes_sent,
+    ),
+];
+
+/// Handshake failures of each endpoint by reason.
+const HANDSHAKE_FAILURES: [HandshakeFailure; 7] = [
+    ("version_mismatch", |s| {
+        s.handshake_failures.version_mismatch
+    }),
+    ("transport_error", |s| s.handshake_failures.transport_error),
+    ("connection_closed", |s| {
+        s.handshake_failures.connection_closed
+    }),
+    ("application_closed", |s| {
+        s.handshake_failures.application_closed
+    }),
+    ("reset", |s| s.handshake_failures.reset),
+    ("timed_out", |s| s.handshake_failures.timed_out),
+    ("locally_closed", |s| s.handshake_failures.locally_closed),
+];
+
+/// Metrics of each connection.
+const CONNECTION_METRICS: [ConnectionMetric; 7] = [
+    (
+        "quinn_connection_rtt_seconds",
+        "gauge",
+        "Current round-trip-time estimate.",
+        |s| s.rtt_us as f64 / 1_000_000.0,
+    ),
+    (
+        "quinn_connection_congestion_window_bytes",
+        "gauge",
+        "Current congestion window.",
+        |s| s.path.cwnd as f64,
+    ),
+    (
+        "quinn_connection_congestion_events_total",
+        "counter",
+        "Congestion events.",
+        |s| s.path.congestion_events as f64,
+    ),
+    (
+        "quinn_connection_sent_bytes_total",
+        "counter",
+        "Bytes sent in UDP datagrams.",
+        |s| s.udp_tx.bytes as f64,
+    ),
+    (
+        "quinn_connection_received_bytes_total",
+        "counter",
+        "Bytes received in UDP datagrams.",
+        |s| s.udp_rx.bytes as f64,
+    ),
+    (
+        "quinn_connection_sent_datagrams_total",
+        "counter",
+        "UDP datagrams sent.",
+        |s| s.udp_tx.datagrams as f64,
+    ),
+    (
+        "quinn_connection_received_datagrams_total",
+        "counter",
+        "UDP datagrams received.",
+        |s| s.udp_rx.datagrams as f64,
+    ),
+];
+
+/// Metrics of a single endpoint at the moment of rendering.
+#[derive(Clone)]
+struct EndpointSnapshot {
+    id: u8,
+    stats: EndpointStats,
+    connections: Vec<(u32, ConnectionStats)>,
+}
+
+/// A registered endpoint with the metrics it had when it was last rendered.
+struct RegisteredEndpoint {
+    endpoint: Weak<Mutex<EndpointImpl>>,
+    last_snapshot: Option<EndpointSnapshot>,
+}
+
+/// Registers an endpoint so its metrics are included by [`render_prometheus`].
+pub fn register_endpoint(endpoint: &Arc<Mutex<EndpointImpl>>) {
+    ENDPOINTS.lock().unwrap().push(RegisteredEndpoint {
+        endpoint: Arc::downgrade(endpoint),
+        last_snapshot: None,
+    });
+}
+
+/// Renders the metrics of all registered endpoints and their connections in the Prometheus text format.
+///
+/// Samples are labeled with the `endpoint` id, connection samples also with the `connection` id.
+/// Endpoints locked at that moment are rendered with the metrics they had when they were last rendered,
+/// connections locked at that moment are left out.
+pub fn render_prometheus() -> String {
+    render(&snapshot_endpoints())
+}
+
+/// Returns the metrics stored by [`store_pending_metrics`] on this thread if they fit in `buffer_len` and were stored
+/// less than a second ago, or else renders them with [`render_prometheus`].
+///
+/// Counters change between calls, this allows a caller to retry with a larger buffer and receive metrics of the reported size,
+/// while a caller that does not retry right away gets current metrics.
+pub fn take_pending_or_render_prometheus(buffer_len: usize) -> String {
+    let pending = PENDING_METRICS.with(|pending| pending.borrow_mut().take());
+
+    match pending {
+        Some((metrics, rendered_at))
+            if metrics.len() <= buffer_len && rendered_at.elapsed() < PENDING_METRICS_LIFETIME =>
+        {
+            metrics
+        }
+        _ => render_prometheus(),
+    }
+}
+
+/// Stores the metrics for the current thread so a retry with [`take_pending_or_render_prometheus`] returns them.
+pub fn store_pending_metrics(metrics: String) {
+    PENDING_METRICS.with(|pending| *pending.borrow_mut() = Some((metrics, Instant::now())));
+}
+
+fn render(snapshots: &[EndpointSnapshot]) -> String {
+    let mut out = String::new();
+
+    for (name, help, value) in ENDPOINT_COUNTERS {
+        write_header(&mut out, name, "counter", help);
+        for snapshot in snapshots {
+            write_sample(
+                &mut out,
+                name,
+                &endpoint_labels(snapshot),
+                value(&snapshot.stats),
+            );
+        }
+    }
+
+    let name = "quinn_endpoint_active_connections";
+    write_header(
+        &mut out,
+        name,
+        "gauge",
+        "Connections currently known to the endpoint.",
+    );
+    for snapshot in snapshots {
+        write_sample(
+            &mut out,
+            name,
+            &endpoint_labels(snapshot),
+            snapshot.stats.active_connections,
+        );
+    }
+
+    let name = "quinn_endpoint_handshake_failures_total";
+    write_header(
+        &mut out,
+        name,
+        "counter",
+        "Connections lost before the handshake completed.",
+    );
+    for snapshot in snapshots {
+        for (reason, value) in HANDSHAKE_FAILURES {
+            let labels = format!("{},reason=\"{}\"", endpoint_labels(snapshot), reason);
+            write_sample(&mut out, name, &labels, value(&snapshot.stats));
+        }
+    }
+
+    for (name, kind, help, value) in CONNECTION_METRICS {
+        write_header(&mut out, name, kind, help);
+        for snapshot in snapshots {
+            for (connection_id, stats) in &snapshot.connections {
+                let labels = format!(
+                    "{},connection=\"{}\"",
+                    endpoint_labels(snapshot),
+                    connection_id
+                );
+                write_sample(&mut out, name, &labels, value(stats));
+            }
+        }
+    }
+
+    out
+}
+
+fn snapshot_endpoints() -> Vec<EndpointSnapshot> {
+    let mut endpoints = ENDPOINTS.lock().unwrap();
+    endpoints.retain(|registered| registered.endpoint.strong_count() > 0);
+
+    let mut snapshots: Vec<_> = endpoints
+        .iter_mut()
+        .filter_map(|registered| {
+            let endpoint = registered.endpoint.upgrade()?;
+
+            // Blocking while holding `ENDPOINTS` could deadlock with a thread that holds the endpoint and waits for `ENDPOINTS`,
+            // busy endpoints are rendered with their last known metrics instead.
+            let snapshot = match endpoint.try_lock() {
+                Ok(endpoint) => {
+                    let mut connections = endpoint.connection_stats();
+                    connections.sort_by_key(|(connection_id, _)| *connection_id);
+
+                    EndpointSnapshot {
+                        id: endpoint.id,
+                        stats: endpoint.stats(),
+                        connections,
+                    }
+                }
+                Err(_) => return registered.last_snapshot.clone(),
+            };
+
+            registered.last_snapshot = Some(snapshot.clone());
+            Some(snapshot)
+        })
+        .collect();
+
+    // Stable output order for scrapes.
+    snapshots.sort_by_key(|snapshot| snapshot.id);
+
+    snapshots
+}
+
+fn endpoint_labels(snapshot: &EndpointSnapshot) -> String {
+    format!("endpoint=\"{}\"", snapshot.id)
+}
+
+fn write_header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn write_sample(out: &mut String, name: &str, labels: &str, value: impl Display) {
+    let _ = writeln!(out, "{}{{{}}} {}", name, labels, value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn samples(out: &str) -> Vec<&str> {
+        out.lines().filter(|line| !line.starts_with('#')).collect()
+    }
+
+    #[test]
+    fn renders_headers_without_endpoints() {
+        let out = render(&[]);
+
+        assert!(samples(&out).is_empty());
+        assert!(out.contains("# TYPE quinn_endpoint_connections_accepted_total counter\n"));
+        assert!(out.contains("# TYPE quinn_connection_rtt_seconds gauge\n"));
+    }
+
+    #[test]
+    fn renders_endpoint_samples() {
+        let mut stats = EndpointStats {
+            connections_accepted: 3,
+            active_connections: 2,
+            ..EndpointStats::default()
+        };
+        stats.handshake_failures.timed_out = 1;
+
+        let out = render(&[EndpointSnapshot {
+            id: 7,
+            stats,
+            connections: Vec::new(),
+        }]);
+        let samples = samples(&out);
+
+        assert!(samples.contains(&"quinn_endpoint_connections_accepted_total{endpoint=\"7\"} 3"));
+        assert!(samples.contains(&"quinn_endpoint_active_connections{endpoint=\"7\"} 2"));
+        assert!(samples.contains(
+            &"quinn_endpoint_handshake_failures_total{endpoint=\"7\",reason=\"timed_out\"} 1"
+        ));
+        assert!(samples.contains(
+            &"quinn_endpoint_handshake_failures_total{endpoint=\"7\",reason=\"reset\"} 0"
+        ));
+        assert_eq!(
+            samples.len(),
+            ENDPOINT_COUNTERS.len() + 1 + HANDSHAKE_FAILURES.len()
+        );
+    }
+
+    #[test]
+    fn renders_connection_samples() {
+        let mut stats = ConnectionStats {
+            rtt_us: 1500,
+            ..ConnectionStats::default()
+        };
+        stats.udp_tx.bytes = 1200;
+
+        let out = render(&[EndpointSnapshot {
+            id: 1,
+            stats: EndpointStats::default(),
+            connections: vec![(4, stats), (5, ConnectionStats::default())],
+        }]);
+        let samples = samples(&out);
+
+        assert!(samples
+            .contains(&"quinn_connection_rtt_seconds{endpoint=\"1\",connection=\"4\"} 0.0015"));
+        assert!(samples
+            .contains(&"quinn_connection_sent_bytes_total{endpoint=\"1\",connection=\"4\"} 1200"));
+        assert!(
+            samples.contains(&"quinn_connection_rtt_seconds{endpoint=\"1\",connection=\"5\"} 0")
+        );
+    }
+
+    #[test]
+    fn every_sample_has_a_header() {
+        let out = render(&[EndpointSnapshot {
+            id: 2,
+            stats: EndpointStats::default(),
+            connections: vec![(1, ConnectionStats::default())],
+        }]);
+
+        for sample in samples(&out) {
+            let name = &sample[..sample.find('{').unwrap()];
+            assert!(out.contains(&format!("# HELP {} ", name)), "{}", name);
+            assert!(out.contains(&format!("# TYPE {} ", name)), "{}", name);
+        }
+    }
+
+    #[test]
+    fn returns_pending_metrics_on_retry() {
+        store_pending_metrics("pending".to_owned());
+
+        assert_eq!(take_pending_or_render_prometheus(7), "pending");
+        assert_ne!(take_pending_or_render_prometheus(7), "pending");
+    }
+
+    #[test]
+    fn renders_again_if_pending_metrics_do_not_fit() {
+        store_pending_metrics("pending".to_owned());
+
+        assert_ne!(take_pending_or_render_prometheus(6), "pending");
+    }
+}
@@ -0,0 +1,239 @@
+use crate::{
+    ffi::callbacks,
+    proto::VarInt,
+    proto_impl::{
+        ConnectionSide,
+        ConnectionStats,
+        FFIErrorKind,
+    },
+};
+use quinn_proto::{
+    Dir,
+    StreamId,
+};
+use std::{
+    fmt::Write as _,
+    fs::{
+        File,
+        OpenOptions,
+    },
+    io::{
+        BufWriter,
+        Write,
+    },
+    path::PathBuf,
+    sync::atomic::{
+        AtomicU64,
+        Ordering,
+    },
+    time::{
+        Instant,
+        SystemTime,
+        UNIX_EPOCH,
+    },
+};
+
+/// Sequence number of the traces written by this process, keeps file names unique when ids are reused.
+static TRACE_SEQUENCE: AtomicU64 = AtomicU64::new(0);
+
+/// Where the qlog traces of the connections of an endpoint are written to.
+#[derive(Debug, Clone)]
+pub enum QlogSink {
+    /// Writes a `.sqlog` file per connection to the directory.
+    ///
+    /// Files are named after the endpoint and connection ids, the start time of the trace in milliseconds since the Unix epoch
+    /// and a sequence number, existing files are never overwritten.
+    Directory(PathBuf),
+    /// Passes each record to the `on_qlog` callback.
+    Callback,
+}
+
+enum QlogOutput {
+    File(BufWriter<File>),
+    Callback(u32),
+}
+
+/// Writes the qlog trace of a connection in the JSON-SEQ format.
+///
+/// Quinn does not expose packet level events, the trace is derived from what the connection observes while polling:
+/// datagrams sent, datagrams received and recovery metrics from the connection statistics, connection and stream state changes.
+pub struct QlogWriter {
+    output: QlogOutput,
+    start: Instant,
+    last_stats: ConnectionStats,
+}
+
+impl QlogWriter {
+    /// Creates the trace of a connection and writes the qlog header.
+    pub fn new(
+        sink: &QlogSink,
+        endpoint_id: u8,
+        connection_id: u32,
+        side: ConnectionSide,
+    ) -> Result<Self, FFIErrorKind> {
+        let vantage_point = match side {
+            ConnectionSide::Client => "client",
+            ConnectionSide::Server => "server",
+        };
+
+        let reference_time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+
+        let output = match sink {
+            QlogSink::Directory(directory) => {
+                // Endpoint ids wrap and connection ids are reused once a connection is dropped.
+                let file_name = format!(
+                    "endpoint{}-connection{}-{}-{}-{}.sqlog",
+                    endpoint_id,
+                    connection_id,
+                    vantage_point,
+                    reference_time,
+                    TRACE_SEQUENCE.fetch_add(1, Ordering::Relaxed)
+                );
+                let file = OpenOptions::new()
+                    .write(true)
+                    .create_new(true)
+                    .open(directory.join(file_name))?;
+                QlogOutput::File(BufWriter::new(file))
+            }
+            QlogSink::Callback => QlogOutput::Callback(connection_id),
+        };
+
+        let mut writer = QlogWriter {
+            output,
+            start: Instant::now(),
+            last_stats: ConnectionStats::default(),
+        };
+
+        writer.write_record(format!(
+            "{{\"qlog_version\":\"0.3\",\"qlog_format\":\"JSON-SEQ\",\"title\":\"endpoint {} connection {}\",\
+             \"trace\":{{\"vantage_point\":{{\"type\":\"{}\"}},\
+             \"common_fields\":{{\"time_format\":\"relative\",\"reference_time\":{}}}}}}}",
+            endpoint_id, connection_id, vantage_point, reference_time
+        ));
+
+        Ok(writer)
+    }
+
+    /// Records a datagram sent by the connection.
+    pub fn datagram_sent(&mut self, len: usize) {
+        self.write_event(
+            "transport:datagrams_sent",
+            format!("{{\"count\":1,\"raw\":[{{\"length\":{}}}]}}", len),
+        );
+    }
+
+    /// Records a connection state change, with the reason when the connection is closed.
+    pub fn connection_state_updated(&mut self, state: &str, reason: Option<&str>) {
+        let mut data = format!("{{\"new\":\"{}\"", state);
+        if let Some(reason) = reason {
+            let _ = write!(data, ",\"reason\":\"{}\"", escape(reason));
+        }
+        data.push('}');
+
+        self.write_event("connectivity:connection_state_updated", data);
+    }
+
+    /// Records a stream state change.
+    pub fn stream_state_updated(&mut self, stream_id: StreamId, state: &str) {
+        let stream_type = match stream_id.dir() {
+            Dir::Bi => "bidirectional",
+            Dir::Uni => "unidirectional",
+        };
+
+        self.write_event(
+            "transport:stream_state_updated",
+            format!(
+                "{{\"stream_id\":{},\"stream_type\":\"{}\",\"new\":\"{}\"}}",
+                VarInt::from(stream_id).into_inner(),
+                stream_type,
+                state
+            ),
+        );
+    }
+
+    /// Records the changes in the connection statistics since the last call and flushes the trace.
+    pub fn stats_updated(&mut self, stats: ConnectionStats) {
+        let last = self.last_stats;
+        self.last_stats = stats;
+
+        if stats.udp_rx.datagrams > last.udp_rx.datagrams {
+            self.write_event(
+                "transport:datagrams_received",
+                format!(
+                    "{{\"count\":{},\"raw\":[{{\"length\":{}}}]}}",
+                    stats.udp_rx.datagrams - last.udp_rx.datagrams,
+                    stats.udp_rx.bytes - last.udp_rx.bytes
+                ),
+            );
+        }
+
+        if stats.path.congestion_events > last.path.congestion_events {
+            self.write_event(
+                "recovery:congestion_state_updated",
+                "{\"new\":\"recovery\",\"trigger\":\"congestion_event\"}".to_owned(),
+            );
+        }
+
+        if stats.rtt_us != last.rtt_us || stats.path.cwnd != last.path.cwnd {
+            self.write_event(
+                "recovery:metrics_updated",
+                format!(
+                    "{{\"smoothed_rtt\":{:.3},\"congestion_window\":{}}}",
+                    stats.rtt_us as f64 / 1000.0,
+                    stats.path.cwnd
+                ),
+            );
+        }
+
+        if let QlogOutput::File(file) = &mut self.output {
+            if let Err(e) = file.flush() {
+                tracing::warn!("Failed to flush qlog trace: {}", e);
+            }
+        }
+    }
+
+    fn write_event(&mut self, name: &str, data: String) {
+        let time = self.start.elapsed().as_secs_f64() * 1000.0;
+        self.write_record(format!(
+            "{{\"time\":{:.3},\"name\":\"{}\",\"data\":{}}}",
+            time, name, data
+        ));
+    }
+
+    /// Writes a JSON-SEQ record: a record separator, the JSON text and a line feed.
+    fn write_record(&mut self, json: String) {
+        let record = format!("\u{1e}{}\n", json);
+
+        match &mut self.output {
+            QlogOutput::File(file) => {
+                if let Err(e) = file.write_all(record.as_bytes()) {
+                    tracing::warn!("Failed to write qlog record: {}", e);
+                }
+            }
+            QlogOutput::Callback(connection_id) => {
+                callbacks::on_qlog(*connection_id, record.as_ptr(), record.len() as u32)
+            }
+        }
+    }
+}
+
+/// Escapes a string for use in a JSON string literal.
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => {
+                let _ = write!(escaped, "\\u{:04x}", c as u32);
+            }
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
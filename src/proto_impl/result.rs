@@ -1,4 +1,6 @@
 use crate::proto::{
+    ConnectError,
+    ConnectionError,
     ReadError,
     WriteError,
 };
@@ -7,17 +9,25 @@ use std::{
     io,
 };
 
-use crate::ffi::FFIResultKind;
+use crate::ffi::{
+    FFIResult,
+    FFIResultKind,
+};
 use quinn_proto::{
+    FinishError,
     ReadableError,
+    UnknownStream,
     VarIntBoundsExceeded,
 };
 use std::{
     error::Error,
-    sync::mpsc::{
-        RecvError,
-        SendError,
-        TryRecvError,
+    sync::{
+        mpsc::{
+            RecvError,
+            SendError,
+            TryRecvError,
+        },
+        PoisonError,
     },
 };
 
@@ -31,6 +41,68 @@ macro_rules! impl_io_error {
             }
         }
     };
+    ($from:path, $category:expr) => {
+        impl From<$from> for FFIErrorKind {
+            fn from(error: $from) -> Self {
+                FFIErrorKind::error($category, &error.to_string())
+            }
+        }
+    };
+}
+
+/// The reason a connection could not be created, reported as error code with `ConnectFailed`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectErrorKind {
+    /// The endpoint can no longer create new connections.
+    EndpointStopping,
+    /// The number of active connections on the endpoint is at the limit.
+    TooManyConnections,
+    /// The server name is malformed.
+    InvalidDnsName,
+    /// The remote address is malformed, e.g. port 0 or an unsupported address family.
+    InvalidRemoteAddress,
+    /// The endpoint has no client configuration.
+    NoDefaultClientConfig,
+    /// The crypto configuration does not support the QUIC version.
+    UnsupportedVersion,
+}
+
+impl From<&ConnectError> for ConnectErrorKind {
+    fn from(error: &ConnectError) -> Self {
+        match error {
+            ConnectError::EndpointStopping => ConnectErrorKind::EndpointStopping,
+            ConnectError::TooManyConnections => ConnectErrorKind::TooManyConnections,
+            ConnectError::InvalidDnsName(_) => ConnectErrorKind::InvalidDnsName,
+            ConnectError::InvalidRemoteAddress(_) => ConnectErrorKind::InvalidRemoteAddress,
+            ConnectError::NoDefaultClientConfig => ConnectErrorKind::NoDefaultClientConfig,
+            ConnectError::UnsupportedVersion => ConnectErrorKind::UnsupportedVersion,
+        }
+    }
+}
+
+/// The category of an error, reported by `last_error_info` so the host can map errors without matching messages.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCategory {
+    /// No error occurred.
+    None,
+    /// Invalid configuration or configuration input.
+    Config,
+    /// TLS, certificate or crypto error.
+    Tls,
+    /// Connecting failed or the connection was lost.
+    Connect,
+    /// Reading from a stream failed.
+    Read,
+    /// Writing to or finishing a stream failed.
+    Write,
+    /// No stream could be opened or accepted.
+    StreamLimit,
+    /// A handle or stream does not refer to a known object.
+    Handle,
+    /// Any other error.
+    Internal,
 }
 
 /// An `Error` implementing type that can be returned in a `Result`.
@@ -44,12 +116,95 @@ pub enum FFIErrorKind {
     FFIError,
     /// IO Error.
     IoError(io::Error),
+    /// A panic caught at the FFI boundary, the backtrace is only captured in debug builds.
+    Panic {
+        message: String,
+        location: String,
+        backtrace: Option<String>,
+    },
+    /// An argument passed by the host is invalid.
+    InvalidArgument(String),
+    /// A connection could not be created.
+    ConnectFailed(ConnectError),
+    /// No connection of the endpoint belongs to the connection handle.
+    UnknownConnection,
+    /// An error with a category and a QUIC transport or application error code, 0 when not applicable.
+    Error {
+        category: ErrorCategory,
+        code: u64,
+        message: String,
+    },
 }
 
 impl FFIErrorKind {
     pub fn io_error(str: &str) -> FFIErrorKind {
         FFIErrorKind::IoError(io::Error::new(io::ErrorKind::Other, str))
     }
+
+    /// Creates an `InvalidArgument` error with the given message.
+    pub fn invalid_argument(message: &str) -> FFIErrorKind {
+        FFIErrorKind::InvalidArgument(message.to_owned())
+    }
+
+    /// Returns the result kind the error is reported with.
+    pub fn result_kind(&self) -> FFIResultKind {
+        match self {
+            FFIErrorKind::FFIResultKind(kind) => kind.clone(),
+            FFIErrorKind::InvalidArgument(_) => FFIResultKind::InvalidArgument,
+            FFIErrorKind::ConnectFailed(_) => FFIResultKind::ConnectFailed,
+            FFIErrorKind::UnknownConnection => FFIResultKind::UnknownConnection,
+            FFIErrorKind::Panic { .. } => FFIResultKind::Panic,
+            _ => FFIResultKind::Error,
+        }
+    }
+
+    /// Creates an error of the given category without error code.
+    pub fn error(category: ErrorCategory, message: &str) -> FFIErrorKind {
+        FFIErrorKind::Error {
+            category,
+            code: 0,
+            message: message.to_owned(),
+        }
+    }
+
+    /// Returns the category of the error.
+    pub fn category(&self) -> ErrorCategory {
+        match self {
+            FFIErrorKind::Error { category, .. } => *category,
+            FFIErrorKind::QuinnError { .. } | FFIErrorKind::ConnectFailed(_) => {
+                ErrorCategory::Connect
+            }
+            FFIErrorKind::InvalidArgument(_) => ErrorCategory::Config,
+            FFIErrorKind::UnknownConnection => ErrorCategory::Handle,
+            FFIErrorKind::FFIResultKind(kind) => match kind {
+                FFIResultKind::ArgumentNull | FFIResultKind::InvalidArgument => {
+                    ErrorCategory::Config
+                }
+                FFIResultKind::HandlePoisoned | FFIResultKind::UnknownConnection => {
+                    ErrorCategory::Handle
+                }
+                FFIResultKind::ConnectFailed => ErrorCategory::Connect,
+                FFIResultKind::BufferBlocked => ErrorCategory::Read,
+                FFIResultKind::WouldBlock => ErrorCategory::Write,
+                _ => ErrorCategory::Internal,
+            },
+            FFIErrorKind::FFIError | FFIErrorKind::IoError(_) | FFIErrorKind::Panic { .. } => {
+                ErrorCategory::Internal
+            }
+        }
+    }
+
+    /// Returns the QUIC transport or application error code of the error, 0 when not applicable.
+    ///
+    /// For `ConnectFailed` the code is the `ConnectErrorKind`.
+    pub fn code(&self) -> u64 {
+        match self {
+            FFIErrorKind::Error { code, .. } => *code,
+            FFIErrorKind::ConnectFailed(error) => ConnectErrorKind::from(error) as u64,
+            FFIErrorKind::QuinnError { code, .. } => *code as u64,
+            _ => 0,
+        }
+    }
 }
 
 impl Error for FFIErrorKind {}
@@ -63,25 +218,133 @@ impl fmt::Display for FFIErrorKind {
             FFIErrorKind::FFIError => write!(f, "Error occurred in the FFI layer"),
             FFIErrorKind::IoError(err) => write!(f, "Io Error Occurred: {}", err.to_string()),
             FFIErrorKind::FFIResultKind(kind) => {
-                write!(f, "Quinn error kind Occurred: {:?}", kind)
+                write!(f, "{:?}: {}", kind, FFIResult::new(kind.clone()))
+            }
+            FFIErrorKind::Error {
+                category, message, ..
+            } => write!(f, "{:?} error: {}", category, message),
+            FFIErrorKind::InvalidArgument(message) => write!(f, "Invalid argument: {}", message),
+            FFIErrorKind::ConnectFailed(error) => write!(f, "Connect failed: {}", error),
+            FFIErrorKind::UnknownConnection => write!(f, "Unknown connection"),
+            FFIErrorKind::Panic {
+                message,
+                location,
+                backtrace,
+            } => {
+                write!(f, "Panic at {}: {}", location, message)?;
+                if let Some(backtrace) = backtrace {
+                    write!(f, "\n{}", backtrace)?;
+                }
+                Ok(())
             }
         }
     }
 }
 
-// For now most protocol errors are treated as IO errors
-impl_io_error!(quinn_proto::ConnectionError);
-impl_io_error!(quinn_proto::TransportError);
 impl_io_error!(io::Error);
 impl_io_error!(TryRecvError);
 impl_io_error!(RecvError);
-impl_io_error!(ReadError);
-impl_io_error!(WriteError);
-impl_io_error!(ReadableError);
-impl_io_error!(VarIntBoundsExceeded);
+impl_io_error!(ReadableError, ErrorCategory::Read);
+impl_io_error!(UnknownStream, ErrorCategory::Handle);
+impl_io_error!(rcgen::RcgenError, ErrorCategory::Tls);
+
+impl_io_error!(rustls::Error, ErrorCategory::Tls);
+
+impl From<quinn_proto::TransportError> for FFIErrorKind {
+    fn from(error: quinn_proto::TransportError) -> Self {
+        FFIErrorKind::Error {
+            category: ErrorCategory::Connect,
+            code: error.code.into(),
+            message: error.to_string(),
+        }
+    }
+}
+
+impl From<ConnectError> for FFIErrorKind {
+    fn from(error: ConnectError) -> Self {
+        FFIErrorKind::ConnectFailed(error)
+    }
+}
+
+impl From<VarIntBoundsExceeded> for FFIErrorKind {
+    fn from(_: VarIntBoundsExceeded) -> Self {
+        FFIErrorKind::invalid_argument(
+            "Value exceeds the maximum QUIC variable-length integer 2^62 - 1",
+        )
+    }
+}
+
+impl From<ConnectionError> for FFIErrorKind {
+    fn from(error: ConnectionError) -> Self {
+        let code = match &error {
+            ConnectionError::TransportError(error) => error.code.into(),
+            ConnectionError::ConnectionClosed(close) => close.error_code.into(),
+            ConnectionError::ApplicationClosed(close) => close.error_code.into_inner(),
+            _ => 0,
+        };
+
+        FFIErrorKind::Error {
+            category: ErrorCategory::Connect,
+            code,
+            message: error.to_string(),
+        }
+    }
+}
+
+impl From<ReadError> for FFIErrorKind {
+    fn from(error: ReadError) -> Self {
+        let code = match &error {
+            ReadError::Reset(code) => code.into_inner(),
+            _ => 0,
+        };
+
+        FFIErrorKind::Error {
+            category: ErrorCategory::Read,
+            code,
+            message: error.to_string(),
+        }
+    }
+}
+
+impl From<WriteError> for FFIErrorKind {
+    fn from(error: WriteError) -> Self {
+        let code = match &error {
+            WriteError::Stopped(code) => code.into_inner(),
+            _ => 0,
+        };
+
+        FFIErrorKind::Error {
+            category: ErrorCategory::Write,
+            code,
+            message: error.to_string(),
+        }
+    }
+}
+
+impl From<FinishError> for FFIErrorKind {
+    fn from(error: FinishError) -> Self {
+        let code = match &error {
+            FinishError::Stopped(code) => code.into_inner(),
+            _ => 0,
+        };
+
+        FFIErrorKind::Error {
+            category: ErrorCategory::Write,
+            code,
+            message: error.to_string(),
+        }
+    }
+}
 
 impl<T> From<SendError<T>> for FFIErrorKind {
     fn from(error: SendError<T>) -> Self {
         FFIErrorKind::IoError(io::Error::new(io::ErrorKind::Other, error.to_string()))
     }
 }
+
+/// A handle mutex is poisoned when a call panicked while holding its lock.
+impl<T> From<PoisonError<T>> for FFIErrorKind {
+    fn from(_: PoisonError<T>) -> Self {
+        FFIErrorKind::FFIResultKind(FFIResultKind::HandlePoisoned)
+    }
+}
@@ -0,0 +1,50 @@
+use crate::proto_impl::FFIErrorKind;
+use std::cell::RefCell;
+
+thread_local!(
+    // Certificate that did not fit in the buffers of the caller, returned again on retry.
+    static PENDING_CERTIFICATE: RefCell<Option<SelfSignedCertificate>> =
+        const { RefCell::new(None) };
+);
+
+/// A DER-encoded self-signed certificate and its private key.
+#[derive(Clone)]
+pub struct SelfSignedCertificate {
+    /// The subject alternative names the certificate was generated for.
+    pub subject_alt_names: Vec<String>,
+    /// The DER-encoded X.509 certificate.
+    pub cert: Vec<u8>,
+    /// The DER-encoded private key in PKCS#8 format.
+    pub key: Vec<u8>,
+}
+
+impl SelfSignedCertificate {
+    /// Generates a new ECDSA P-256 certificate for the given DNS names.
+    pub fn generate(subject_alt_names: Vec<String>) -> Result<Self, FFIErrorKind> {
+        let certificate = rcgen::generate_simple_self_signed(subject_alt_names.clone())?;
+
+        Ok(SelfSignedCertificate {
+            subject_alt_names,
+            cert: certificate.serialize_der()?,
+            key: certificate.serialize_private_key_der(),
+        })
+    }
+
+    /// Returns the certificate stored by [`SelfSignedCertificate::store_pending`] if it was generated for the same names,
+    /// or else generates a new certificate.
+    ///
+    /// Signatures are randomized, this allows a caller to retry with larger buffers and receive a certificate of the reported size.
+    pub fn take_pending_or_generate(subject_alt_names: Vec<String>) -> Result<Self, FFIErrorKind> {
+        let pending = PENDING_CERTIFICATE.with(|pending| pending.borrow_mut().take());
+
+        match pending {
+            Some(pending) if pending.subject_alt_names == subject_alt_names => Ok(pending),
+            _ => Self::generate(subject_alt_names),
+        }
+    }
+
+    /// Stores the certificate for the current thread so the next call to [`SelfSignedCertificate::take_pending_or_generate`] returns it.
+    pub fn store_pending(self) {
+        PENDING_CERTIFICATE.with(|pending| *pending.borrow_mut() = Some(self));
+    }
+}
@@ -0,0 +1,178 @@
+use crate::proto_impl::{
+    ErrorCategory,
+    FFIErrorKind,
+};
+use rustls::client::StoresClientSessions;
+use std::{
+    collections::{
+        HashMap,
+        VecDeque,
+    },
+    convert::TryInto,
+    sync::Mutex,
+};
+
+/// Maximum number of stored client sessions, the oldest session is dropped when exceeded.
+const MAX_SESSIONS: usize = 256;
+
+/// Client session cache that stores TLS resumption tickets.
+///
+/// The cache can be serialized to bytes so resumption tickets survive process restarts.
+#[derive(Default)]
+pub struct SessionCache {
+    state: Mutex<SessionCacheState>,
+}
+
+#[derive(Default)]
+struct SessionCacheState {
+    sessions: HashMap<Vec<u8>, Vec<u8>>,
+    insertion_order: VecDeque<Vec<u8>>,
+}
+
+impl SessionCacheState {
+    fn insert(&mut self, key: Vec<u8>, value: Vec<u8>) {
+        if self.sessions.insert(key.clone(), value).is_none() {
+            self.insertion_order.push_back(key);
+        }
+
+        while self.sessions.len() > MAX_SESSIONS {
+            if let Some(oldest) = self.insertion_order.pop_front() {
+                self.sessions.remove(&oldest);
+            }
+        }
+    }
+}
+
+impl SessionCache {
+    /// Serializes all stored sessions.
+    ///
+    /// Each session is written as a little-endian `u32` key length, the key, a little-endian `u32` value length and the value.
+    pub fn serialize(&self) -> Vec<u8> {
+        let state = self.state.lock().unwrap();
+        let mut bytes = Vec::new();
+
+        for key in &state.insertion_order {
+            let value = &state.sessions[key];
+
+            bytes.extend_from_slice(&(key.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(key);
+            bytes.extend_from_slice(&(value.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(value);
+        }
+
+        bytes
+    }
+
+    /// Restores sessions serialized by [`SessionCache::serialize`].
+    ///
+    /// Restored sessions are added to the sessions already stored.
+    pub fn deserialize(&self, mut bytes: &[u8]) -> Result<(), FFIErrorKind> {
+        let mut sessions = Vec::new();
+
+        while !bytes.is_empty() {
+            let key = read_length_prefixed(&mut bytes)?;
+            let value = read_length_prefixed(&mut bytes)?;
+            sessions.push((key, value));
+        }
+
+        let mut state = self.state.lock().unwrap();
+        for (key, value) in sessions {
+            state.insert(key, value);
+        }
+
+        Ok(())
+    }
+}
+
+impl StoresClientSessions for SessionCache {
+    fn put(&self, key: Vec<u8>, value: Vec<u8>) -> bool {
+        self.state.lock().unwrap().insert(key, value);
+        true
+    }
+
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+        self.state.lock().unwrap().sessions.get(key).cloned()
+    }
+}
+
+fn read_length_prefixed(bytes: &mut &[u8]) -> Result<Vec<u8>, FFIErrorKind> {
+    let malformed = || FFIErrorKind::error(ErrorCategory::Config, "Malformed session cache data");
+
+    if bytes.len() < 4 {
+        return Err(malformed());
+    }
+
+    let (len, rest) = bytes.split_at(4);
+    let len = u32::from_le_bytes(len.try_into().unwrap()) as usize;
+
+    if rest.len() < len {
+        return Err(malformed());
+    }
+
+    let (value, rest) = rest.split_at(len);
+    *bytes = rest;
+
+    Ok(value.to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_sessions() {
+        let cache = SessionCache::default();
+        cache.put(b"example.com".to_vec(), b"ticket".to_vec());
+        cache.put(b"other.com".to_vec(), b"other ticket".to_vec());
+
+        let restored = SessionCache::default();
+        restored.deserialize(&cache.serialize()).unwrap();
+
+        assert_eq!(restored.get(b"example.com"), Some(b"ticket".to_vec()));
+        assert_eq!(restored.get(b"other.com"), Some(b"other ticket".to_vec()));
+        assert_eq!(restored.serialize(), cache.serialize());
+    }
+
+    #[test]
+    fn round_trips_zero_length_entries() {
+        let cache = SessionCache::default();
+        cache.put(Vec::new(), Vec::new());
+
+        let bytes = cache.serialize();
+        assert_eq!(bytes, [0; 8]);
+
+        let restored = SessionCache::default();
+        restored.deserialize(&bytes).unwrap();
+        assert_eq!(restored.get(b""), Some(Vec::new()));
+    }
+
+    #[test]
+    fn deserializes_empty_input() {
+        let cache = SessionCache::default();
+        cache.deserialize(&[]).unwrap();
+        assert!(cache.serialize().is_empty());
+    }
+
+    #[test]
+    fn rejects_truncated_length() {
+        let cache = SessionCache::default();
+        assert!(cache.deserialize(&[1, 0]).is_err());
+    }
+
+    #[test]
+    fn rejects_truncated_value() {
+        let source = SessionCache::default();
+        source.put(b"key".to_vec(), b"value".to_vec());
+        let bytes = source.serialize();
+
+        let cache = SessionCache::default();
+        assert!(cache.deserialize(&bytes[..bytes.len() - 1]).is_err());
+        assert!(cache.get(b"key").is_none());
+    }
+
+    #[test]
+    fn rejects_missing_value() {
+        let cache = SessionCache::default();
+        assert!(cache.deserialize(&[3, 0, 0, 0, b'k', b'e', b'y']).is_err());
+    }
+}
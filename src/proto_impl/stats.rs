@@ -0,0 +1,205 @@
+use crate::proto;
+
+/// Statistics of a connection, see [`proto::ConnectionStats`].
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ConnectionStats {
+    /// Current best estimate of the round-trip-time of the connection, in microseconds.
+    pub rtt_us: u64,
+    /// Statistics of the current transmission path.
+    pub path: PathStats,
+    /// Statistics of the UDP datagrams transmitted.
+    pub udp_tx: UdpStats,
+    /// Statistics of the UDP datagrams received.
+    pub udp_rx: UdpStats,
+    /// Statistics of the frames transmitted.
+    pub frame_tx: FrameStats,
+    /// Statistics of the frames received.
+    pub frame_rx: FrameStats,
+}
+
+/// Statistics of a transmission path.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct PathStats {
+    /// Current congestion window of the connection, in bytes.
+    pub cwnd: u64,
+    /// Congestion events on the connection.
+    pub congestion_events: u64,
+}
+
+/// Statistics of the UDP datagrams of a connection.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct UdpStats {
+    /// The number of UDP datagrams.
+    pub datagrams: u64,
+    /// The number of bytes transferred inside UDP datagrams.
+    pub bytes: u64,
+    /// The number of transmit calls.
+    pub transmits: u64,
+}
+
+/// Number of frames by frame type.
+///
+/// These are all the frame counters quinn-proto 0.8 exposes,
+/// it does not expose packet loss or the bytes in flight of a connection.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FrameStats {
+    /// ACK frames.
+    pub acks: u64,
+    /// CRYPTO frames, carrying TLS handshake data.
+    pub crypto: u64,
+    /// CONNECTION_CLOSE frames.
+    pub connection_close: u64,
+    /// DATA_BLOCKED frames, sent when connection level flow control blocks sending.
+    pub data_blocked: u64,
+    /// DATAGRAM frames, carrying unreliable application datagrams.
+    pub datagram: u64,
+    /// HANDSHAKE_DONE frames.
+    pub handshake_done: u64,
+    /// MAX_DATA frames, granting connection level flow control credit.
+    pub max_data: u64,
+    /// MAX_STREAM_DATA frames, granting stream level flow control credit.
+    pub max_stream_data: u64,
+    /// MAX_STREAMS frames raising the bidirectional stream limit.
+    pub max_streams_bidi: u64,
+    /// MAX_STREAMS frames raising the unidirectional stream limit.
+    pub max_streams_uni: u64,
+    /// NEW_CONNECTION_ID frames.
+    pub new_connection_id: u64,
+    /// NEW_TOKEN frames, carrying address validation tokens for future connections.
+    pub new_token: u64,
+    /// PATH_CHALLENGE frames.
+    pub path_challenge: u64,
+    /// PATH_RESPONSE frames.
+    pub path_response: u64,
+    /// PING frames.
+    pub ping: u64,
+    /// RESET_STREAM frames, abandoning the send side of a stream.
+    pub reset_stream: u64,
+    /// RETIRE_CONNECTION_ID frames.
+    pub retire_connection_id: u64,
+    /// STREAM_DATA_BLOCKED frames, sent when stream level flow control blocks sending.
+    pub stream_data_blocked: u64,
+    /// STREAMS_BLOCKED frames, sent when the bidirectional stream limit blocks opening streams.
+    pub streams_blocked_bidi: u64,
+    /// STREAMS_BLOCKED frames, sent when the unidirectional stream limit blocks opening streams.
+    pub streams_blocked_uni: u64,
+    /// STOP_SENDING frames, asking the peer to stop sending on a stream.
+    pub stop_sending: u64,
+    /// STREAM frames, carrying stream data.
+    pub stream: u64,
+}
+
+// Quinn does not export its UDP and frame statistics types, their fields are copied by name.
+macro_rules! copy_stats {
+    ($stats:expr => $target:ident { $($field:ident),* }) => {
+        $target {
+            $($field: $stats.$field as u64),*
+        }
+    };
+}
+
+macro_rules! udp_stats {
+    ($stats:expr) => {
+        copy_stats!($stats => UdpStats { datagrams, bytes, transmits })
+    };
+}
+
+macro_rules! frame_stats {
+    ($stats:expr) => {
+        copy_stats!($stats => FrameStats {
+            acks, crypto, connection_close, data_blocked, datagram, handshake_done, max_data, max_stream_data,
+            max_streams_bidi, max_streams_uni, new_connection_id, new_token, path_challenge, path_response, ping,
+            reset_stream, retire_connection_id, stream_data_blocked, streams_blocked_bidi, streams_blocked_uni,
+            stop_sending, stream
+        })
+    };
+}
+
+impl From<&proto::Connection> for ConnectionStats {
+    fn from(connection: &proto::Connection) -> Self {
+        let stats = connection.stats();
+
+        ConnectionStats {
+            rtt_us: connection.rtt().as_micros() as u64,
+            path: PathStats {
+                cwnd: stats.path.cwnd,
+                congestion_events: stats.path.congestion_events,
+            },
+            udp_tx: udp_stats!(stats.udp_tx),
+            udp_rx: udp_stats!(stats.udp_rx),
+            frame_tx: frame_stats!(stats.frame_tx),
+            frame_rx: frame_stats!(stats.frame_rx),
+        }
+    }
+}
+
+/// Counters of an endpoint, see [`crate::proto_impl::EndpointImpl::stats`].
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct EndpointStats {
+    /// Incoming connections accepted.
+    pub connections_accepted: u64,
+    /// Outgoing connections initiated.
+    pub connections_initiated: u64,
+    /// Incoming handshakes answered without creating a connection, because they were refused or asked to retry.
+    ///
+    /// Counted from the packet the endpoint answers an initial packet with, version negotiation is not counted.
+    pub connections_rejected: u64,
+    /// Connections currently known to the endpoint.
+    pub active_connections: u64,
+    /// Datagrams passed to `handle_datagram`.
+    pub datagrams_received: u64,
+    /// Datagrams that did not belong to a connection and did not create one.
+    pub datagrams_dropped: u64,
+    /// Transmits passed to the `on_transmit` callback.
+    pub transmits: u64,
+    /// Bytes of the datagrams received.
+    pub bytes_received: u64,
+    /// Bytes of the transmits sent.
+    pub bytes_sent: u64,
+    /// Connections lost before the handshake completed, by reason.
+    pub handshake_failures: HandshakeFailures,
+}
+
+/// Number of connections lost before the handshake completed, by reason.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct HandshakeFailures {
+    /// The peer does not support a compatible QUIC version.
+    pub version_mismatch: u64,
+    /// The peer violated the protocol, this includes TLS failures.
+    pub transport_error: u64,
+    /// The peer closed the connection.
+    pub connection_closed: u64,
+    /// The peer application closed the connection.
+    pub application_closed: u64,
+    /// The peer reset the connection.
+    pub reset: u64,
+    /// The handshake timed out.
+    pub timed_out: u64,
+    /// The connection was closed locally.
+    pub locally_closed: u64,
+}
+
+impl HandshakeFailures {
+    /// Counts a handshake that failed because of the given error.
+    pub fn record(&mut self, error: &proto::ConnectionError) {
+        use proto::ConnectionError::*;
+
+        let counter = match error {
+            VersionMismatch => &mut self.version_mismatch,
+            TransportError(_) => &mut self.transport_error,
+            ConnectionClosed(_) => &mut self.connection_closed,
+            ApplicationClosed(_) => &mut self.application_closed,
+            Reset => &mut self.reset,
+            TimedOut => &mut self.timed_out,
+            LocallyClosed => &mut self.locally_closed,
+        };
+
+        *counter += 1;
+    }
+}
@@ -0,0 +1,35 @@
+/// Statistics of a single stream, counted by the connection as the client application reads and writes.
+///
+/// Quinn does not report how many bytes of a stream the peer acknowledged, only `acked` once all data is acknowledged.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StreamStats {
+    /// Bytes written to the stream, including bytes quinn has not sent yet.
+    pub bytes_written: u64,
+    /// Bytes buffered by `write_all` that are not yet written to the stream.
+    pub bytes_buffered: u64,
+    /// Bytes read from the stream.
+    pub bytes_received: u64,
+    /// Whether the send side is finished.
+    pub finished: bool,
+    /// Whether the peer acknowledged all data of the finished send side.
+    pub acked: bool,
+    /// Whether the peer stopped the send side.
+    pub stopped: bool,
+    /// Whether all data of the receive side, finished by the peer, has been read.
+    pub received_fin: bool,
+    /// Whether the peer reset the receive side.
+    pub reset: bool,
+    /// Whether the last write was limited by flow control, no credit is left until the stream becomes writable.
+    pub write_blocked: bool,
+}
+
+impl StreamStats {
+    /// Returns whether the sides the stream has are both closed, after which no statistics change anymore.
+    pub(crate) fn is_closed(&self, has_send: bool, has_recv: bool) -> bool {
+        let send_closed = !has_send || self.acked || self.stopped;
+        let recv_closed = !has_recv || self.received_fin || self.reset;
+
+        send_closed && recv_closed
+    }
+}